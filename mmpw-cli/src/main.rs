@@ -1,6 +1,16 @@
-use mmpw_gen::{go_random, permutate, prepare_words, Word, NAMES, SIX_LETTER_WORDS};
-use mmpw_validate::{binstring, Password};
-use std::path::{Path, PathBuf};
+use mmpw_gen::{
+    go_random, permutate_from, permutate_parallel, prepare_words, solve, Checkpoint, Progress,
+    SlicePermutations, Word, NAMES, SIX_LETTER_WORDS,
+};
+use mmpw_validate::{
+    binstring, decode, diff, names, recover, rekey, valid_edits, Edit, ParsePassword, Password,
+    RekeyError, MAX_EDITS,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
 use structopt::StructOpt;
 
 pub fn load_words(path: &Path) -> Vec<[u8; 6]> {
@@ -9,6 +19,48 @@ pub fn load_words(path: &Path) -> Vec<[u8; 6]> {
     prepare_words(words).unwrap()
 }
 
+/// Most spellings --equivalent-names prints
+const MAX_SPELLINGS: usize = 100;
+/// How often --checkpoint saves the search position
+const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Parse a 1-based shard like `3/8`
+fn parse_shard(text: &str) -> Result<(u64, u64), String> {
+    let err = || format!("{:?} is not a shard like 3/8", text);
+    let (index, count) = text.split_once('/').ok_or_else(err)?;
+    let index: u64 = index.trim().parse().map_err(|_| err())?;
+    let count: u64 = count.trim().parse().map_err(|_| err())?;
+    if index == 0 || index > count {
+        return Err(format!("shard {} of {} doesn't exist", index, count));
+    }
+    Ok((index, count))
+}
+
+/// Parse how many characters --edits may change, which gets slow fast
+fn parse_max_edits(text: &str) -> Result<usize, String> {
+    let max_edits: usize = text.parse().map_err(|e| format!("{}", e))?;
+    if max_edits == 0 || max_edits > MAX_EDITS {
+        return Err(format!("must be between 1 and {}", MAX_EDITS));
+    }
+    Ok(max_edits)
+}
+
+/// What --checkpoint saves: how far the search got, and for which name and shard
+#[derive(Serialize, Deserialize)]
+struct SavedSearch {
+    name: String,
+    /// 1-based like --shard, `None` for the whole search
+    shard: Option<(u64, u64)>,
+    checkpoint: Checkpoint,
+}
+
+fn describe_shard(shard: Option<(u64, u64)>) -> String {
+    match shard {
+        Some((index, count)) => format!("shard {}/{}", index, count),
+        None => "the whole search".to_owned(),
+    }
+}
+
 #[derive(StructOpt)]
 struct Opt {
     /// The name you want to use in the password. It will only work with this name.
@@ -25,6 +77,43 @@ struct Opt {
     /// Try the same passwords with different names in hopes of it becoming valid with at least one.
     #[structopt(short, long)]
     brute_force_with_names: bool,
+    /// Find the same passwords with a much faster meet-in-the-middle search, without progress reports.
+    #[structopt(short, long)]
+    meet_in_the_middle: bool,
+    /// Save the search position to this file, and resume from it if it exists. Searches on one thread.
+    /// Passwords found since the last save are printed again after resuming.
+    #[structopt(long, conflicts_with_all = &["random", "brute-force-with-names", "meet-in-the-middle", "threads"])]
+    checkpoint: Option<PathBuf>,
+    /// Only search part of the passwords, like 3/8 for the third of eight parts. Searches on one thread.
+    #[structopt(long, parse(try_from_str = parse_shard), conflicts_with_all = &["random", "brute-force-with-names", "meet-in-the-middle", "threads"])]
+    shard: Option<(u64, u64)>,
+    /// How many threads to search with. Defaults to one per CPU.
+    #[structopt(short = "j", long)]
+    threads: Option<usize>,
+    /// List every valid password up to --max-edits characters away from this one instead of searching.
+    #[structopt(short, long, conflicts_with_all = &["recover", "equivalent-names", "rekey", "diff", "random", "brute-force-with-names", "meet-in-the-middle", "checkpoint", "shard"])]
+    edits: Option<String>,
+    /// How many characters --edits may change, at most 2.
+    #[structopt(long, default_value = "1", parse(try_from_str = parse_max_edits))]
+    max_edits: usize,
+    /// Guess which valid passwords a mistyped one was meant to be instead of searching.
+    #[structopt(long, conflicts_with_all = &["equivalent-names", "rekey", "diff", "random", "brute-force-with-names", "meet-in-the-middle", "checkpoint", "shard"])]
+    recover: Option<String>,
+    /// List the shortest names that work with the same passwords as this one instead of searching.
+    #[structopt(long, conflicts_with_all = &["rekey", "diff", "random", "brute-force-with-names", "meet-in-the-middle", "checkpoint", "shard"])]
+    equivalent_names: bool,
+    /// Move this password to the name given with --new-name instead of searching.
+    #[structopt(long, requires = "new-name", conflicts_with_all = &["diff", "random", "brute-force-with-names", "meet-in-the-middle", "checkpoint", "shard"])]
+    rekey: Option<String>,
+    /// The name --rekey makes the password for.
+    #[structopt(long, requires = "rekey")]
+    new_name: Option<String>,
+    /// Show which parts of the game state differ between two passwords instead of searching.
+    #[structopt(long, number_of_values = 2, value_names = &["OLD", "NEW"], conflicts_with_all = &["random", "brute-force-with-names", "meet-in-the-middle", "checkpoint", "shard"])]
+    diff: Option<Vec<String>>,
+    /// The name of the second --diff password, if it's not for the same name.
+    #[structopt(long, requires = "diff")]
+    other_name: Option<String>,
 }
 
 fn main() {
@@ -42,25 +131,216 @@ fn main() {
         }
     };
 
-    let mut key_name_pairs = vec![(binstring::hash_name(opt.name.as_bytes()), &opt.name[..])];
+    let key = match binstring::try_hash_name(&opt.name) {
+        Ok(key) => key,
+        Err(e) => {
+            eprintln!("Invalid name: {}", e);
+            std::process::exit(1);
+        }
+    };
+    if opt.equivalent_names {
+        let shortest = names::shortest_equivalent(&opt.name).unwrap();
+        println!("shortest equivalent name: {}", shortest);
+        let mut spellings = names::spellings(&shortest);
+        for spelling in spellings.by_ref().take(MAX_SPELLINGS) {
+            println!("{}", spelling);
+        }
+        if spellings.next().is_some() {
+            eprintln!("Only showing the first {} spellings", MAX_SPELLINGS);
+        }
+        return;
+    }
+
+    if let Some(texts) = opt.diff {
+        let new_key = match opt.other_name.as_deref().map(binstring::try_hash_name) {
+            Some(Ok(key)) => key,
+            Some(Err(e)) => {
+                eprintln!("Invalid name: {}", e);
+                std::process::exit(1);
+            }
+            None => key.clone(),
+        };
+        let decoded = Password::parse(&texts[0])
+            .and_then(|pw| decode(&pw, &key))
+            .and_then(|old| Ok((old, decode(&Password::parse(&texts[1])?, &new_key)?)));
+        match decoded {
+            Ok((old, new)) => println!("{}", diff(&old, &new)),
+            Err(e) => {
+                eprintln!("Invalid password: {}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    if let (Some(text), Some(new_name)) = (opt.rekey, opt.new_name) {
+        let old_name = &opt.name;
+        let rekeyed = Password::parse(&text)
+            .map_err(RekeyError::from)
+            .and_then(|pw| rekey(&pw, old_name, &new_name));
+        match rekeyed {
+            Ok(pw) => show(&pw, &new_name),
+            Err(e) => {
+                eprintln!("Can't move password: {}", e);
+                std::process::exit(1);
+            }
+        }
+        return;
+    }
+
+    if let Some(text) = opt.edits {
+        let pw = match Password::parse(&text) {
+            Ok(pw) => pw,
+            Err(e) => {
+                eprintln!("Invalid password: {}", e);
+                std::process::exit(1);
+            }
+        };
+        let edits = valid_edits(&pw, &key, opt.max_edits);
+        for edit in &edits {
+            show_edit(edit, &opt.name);
+        }
+        eprintln!("Finished. Found {} valid passwords", edits.len());
+        return;
+    }
+
+    if let Some(text) = opt.recover {
+        let found = match recover(&text, &key) {
+            Ok(found) => found,
+            Err(e) => {
+                eprintln!("Invalid password: {}", e);
+                std::process::exit(1);
+            }
+        };
+        for recovery in &found {
+            show(&recovery.password, &opt.name);
+            println!(
+                "    typos: {} oddities: {} rank: {} cash: {}",
+                recovery.edits, recovery.oddities, recovery.data.rank, recovery.data.cash
+            );
+        }
+        eprintln!("Finished. Found {} valid passwords", found.len());
+        return;
+    }
+
+    if opt.checkpoint.is_some() || opt.shard.is_some() {
+        let count = search_resumable(&key, words, &opt.name, opt.checkpoint.as_deref(), opt.shard);
+        eprintln!("Finished. Found {} valid passwords", count);
+        return;
+    }
+
+    let mut key_name_pairs = vec![(key, &opt.name[..])];
 
     if opt.brute_force_with_names {
         for name in NAMES.iter() {
-            key_name_pairs.push((binstring::hash_name(name.as_bytes()), name));
+            // Equivalent names would only find the same passwords again
+            if key_name_pairs
+                .iter()
+                .all(|&(_, other)| !names::names_equivalent(name, other))
+            {
+                key_name_pairs.push((binstring::hash_name(name.as_bytes()), name));
+            }
         }
     }
 
     if opt.random {
-        go_random(&key_name_pairs[0].0, words, key_name_pairs[0].1);
+        for found in go_random(&key_name_pairs[0].0, words, key_name_pairs[0].1) {
+            show(&found.password, found.name);
+        }
     } else {
+        let threads = opt.threads.unwrap_or_else(|| {
+            std::thread::available_parallelism().map_or(1, std::num::NonZeroUsize::get)
+        });
         let mut count = 0;
         for (key, name) in key_name_pairs {
-            count += permutate(&key, words, name, show);
+            count += if opt.meet_in_the_middle {
+                solve(&key, words, name, show)
+            } else {
+                permutate_parallel(&key, words, name, threads, show_progress, show)
+            };
         }
         eprintln!("Finished. Found {} valid passwords", count);
     }
 }
 
+/// Search one shard on one thread, saving and resuming from `checkpoint_path`
+fn search_resumable(
+    key: &binstring::BinString,
+    words: &[Word],
+    name: &str,
+    checkpoint_path: Option<&Path>,
+    shard: Option<(u64, u64)>,
+) -> usize {
+    let saved = checkpoint_path.filter(|path| path.exists()).map(|path| {
+        let text = std::fs::read_to_string(path).unwrap();
+        let saved: SavedSearch = serde_json::from_str(&text).unwrap_or_else(|e| {
+            eprintln!("Invalid checkpoint {}: {}", path.display(), e);
+            std::process::exit(1);
+        });
+        if !names::names_equivalent(&saved.name, name) {
+            eprintln!("Can't resume: checkpoint is for the name {}", saved.name);
+            std::process::exit(1);
+        }
+        if saved.shard != shard {
+            eprintln!(
+                "Can't resume {}: checkpoint is for {}",
+                describe_shard(shard),
+                describe_shard(saved.shard)
+            );
+            std::process::exit(1);
+        }
+        saved.checkpoint
+    });
+    let from = saved.unwrap_or_else(|| {
+        let pairs = SlicePermutations::<_, 2>::new(words);
+        match shard {
+            Some((index, count)) => pairs.shard(index - 1, count).checkpoint(),
+            None => pairs.checkpoint(),
+        }
+    });
+    let save = |checkpoint: &Checkpoint| {
+        if let Some(path) = checkpoint_path {
+            let saved = SavedSearch {
+                name: name.to_owned(),
+                shard,
+                checkpoint: *checkpoint,
+            };
+            // Write to a temporary file first so an interrupted save doesn't lose the checkpoint
+            let tmp = path.with_extension("tmp");
+            std::fs::write(&tmp, serde_json::to_string(&saved).unwrap()).unwrap();
+            std::fs::rename(&tmp, path).unwrap();
+        }
+    };
+    let mut last_save = Instant::now();
+    let result = permutate_from(
+        key,
+        words,
+        name,
+        &from,
+        |checkpoint| {
+            if last_save.elapsed() >= CHECKPOINT_INTERVAL || checkpoint.is_done() {
+                save(checkpoint);
+                last_save = Instant::now();
+            }
+        },
+        show,
+    );
+    result.unwrap_or_else(|e| {
+        eprintln!("Can't resume: {}", e);
+        std::process::exit(1);
+    })
+}
+
+fn show_progress(progress: Progress) {
+    eprint!(
+        "\r{:>5.1}%\r",
+        progress.done as f64 * 100. / progress.total as f64
+    );
+    if progress.done == progress.total {
+        eprintln!();
+    }
+}
+
 fn show(pw: &Password, name: &str) {
     let utf = std::str::from_utf8(pw).unwrap();
     println!(
@@ -71,3 +351,17 @@ fn show(pw: &Password, name: &str) {
         &utf[12..]
     );
 }
+
+fn show_edit(edit: &Edit, name: &str) {
+    show(&edit.password, name);
+    for change in &edit.changes {
+        let fields: Vec<&str> = change.fields.iter().map(|field| field.name).collect();
+        println!(
+            "    position {}: {} -> {} ({})",
+            change.pos + 1,
+            change.from as char,
+            change.to as char,
+            fields.join(", ")
+        );
+    }
+}
@@ -2,7 +2,12 @@ use fltk::{
     app, button::*, enums::*, frame::Frame, group::*, input::*, menu::*, output::*, prelude::*,
     valuator::*, window::*,
 };
-use mmpw_validate::{binstring::BinString, CKSUM_BITS};
+use mmpw_validate::{
+    binstring::{self, BinString},
+    layout,
+    player_data::ITEM_NAMES,
+    PlayerData,
+};
 
 const PASSWORD_CASH: [u32; 64] = [
     0, 100, 200, 300, 400, 500, 700, 900, 1100, 1300, 1500, 1600, 1900, 2100, 2400, 2700, 3100,
@@ -44,93 +49,6 @@ fn find_closest_match(item: u32, set: &[u32; 64]) -> usize {
     }
 }
 
-const ITEM_NAMES: [&str; 30] = [
-    "Cornstarch Cookies",
-    "Graphing Calculator",
-    "Hydrogen Peroxide",
-    "Acetone",
-    "Romantic Incense",
-    "Puzzle Keys",
-    "Diamond Puzzle Key",
-    "Mars Software",
-    "Venus Software",
-    "Magnetic Desk Toy",
-    "Grey Anal Beads",
-    "Purple Anal Beads",
-    "Huge Glass Anal Beads",
-    "Huge Grey Anal Beads",
-    "Fruit Bugs",
-    "Marshmallow Bugs",
-    "Spooky Bugs",
-    "RetroPie",
-    "Assorted Gloves",
-    "Insulated Gloves",
-    "Ghost Gloves",
-    "Vanishing Gloves",
-    "Streaming Package",
-    "Vibrator",
-    "Blue Dildo",
-    "Gummy Dildo",
-    "Huge Dildo",
-    "Happy Meal Toys",
-    "Pizza Coupons",
-    "???",
-];
-
-pub struct PlayerData {
-    mystery_box_status: u16,
-    abra_bead_capacity: u8,
-    items: [bool; 30],
-    chat_states: [u8; 10],
-    cash: u32,
-    abra_story: u8,
-    final_trial_count: u8,
-    rank: u8,
-    time_played: u32,
-    five_peg: bool,
-    seven_peg: bool,
-}
-
-pub fn encode(player_data: &PlayerData) -> BinString {
-    let mut bs = BinString::zeroed();
-    let n_items = player_data.items.iter().filter(|b| **b).count();
-    let has_every_item = n_items == 30;
-    let mut writer = bs.writer();
-    if has_every_item {
-        writer.write_int::<1>(1);
-        writer.write_int::<12>(player_data.mystery_box_status as i32);
-        writer.write_int::<8>(player_data.abra_bead_capacity as i32);
-        writer.write_int::<10>(326);
-    } else {
-        writer.write_int::<1>(0);
-        for i in 0..30 {
-            writer.write_int::<1>(if player_data.items[i] { 1 } else { 0 });
-        }
-    }
-    writer.write_int::<2>(player_data.chat_states[0] as i32);
-    writer.write_int::<3>(player_data.chat_states[1] as i32);
-    writer.write_int::<2>(player_data.chat_states[2] as i32);
-    writer.write_int::<2>(player_data.chat_states[3] as i32);
-    writer.write_int::<3>(player_data.chat_states[4] as i32);
-    writer.write_int::<2>(player_data.chat_states[5] as i32);
-    writer.write_int::<2>(player_data.chat_states[6] as i32);
-    writer.write_int::<2>(player_data.chat_states[7] as i32);
-    writer.write_int::<2>(player_data.chat_states[8] as i32);
-    writer.write_int::<2>(player_data.chat_states[9] as i32);
-    writer.write_int::<6>(player_data.cash as i32);
-    writer.write_int::<3>(player_data.abra_story as i32);
-    writer.write_int::<3>(player_data.final_trial_count as i32);
-    writer.write_int::<7>(player_data.rank as i32);
-    writer.write_int::<6>(player_data.time_played as i32);
-    writer.write_int::<1>(if player_data.five_peg { 1 } else { 0 });
-    writer.write_int::<1>(if player_data.seven_peg { 1 } else { 0 });
-    let checksum = bs.calc_checksum();
-    let mut writer = bs.writer();
-    writer.skip(81);
-    writer.write_int::<CKSUM_BITS>(checksum as i32);
-    bs
-}
-
 fn bounded_int_input(label: &str, min: i32, max: i32) -> ValueInput {
     // Make default size very tiny so we know size wasn't explicitly set
     let mut inp = ValueInput::default().with_label(label).with_size(16, 16);
@@ -177,7 +95,7 @@ fn main() {
         .with_pos(100, 32);
     pack.set_spacing(8);
     let mut name_inp = Input::default().with_label("Name").with_size(0, 32);
-    name_inp.set_maximum_size(17);
+    name_inp.set_maximum_size(binstring::MAX_NAME_LEN as i32);
     name_inp.set_trigger(CallbackTrigger::Changed);
     name_inp.emit(s, Msg::NameInpChanged);
     let mut pack2 = Pack::default().with_size(0, 32);
@@ -191,7 +109,11 @@ fn main() {
     let mut pack2 = Pack::default().with_size(0, 32);
     pack2.set_spacing(8);
     pack2.set_type(PackType::Horizontal);
-    let mut rank_inp = bounded_int_input("Puzzle rank", 0, 65);
+    let mut rank_inp = bounded_int_input(
+        "Puzzle rank",
+        layout::RANK.min.into(),
+        layout::RANK.max.into(),
+    );
     rank_inp.set_size(32, 0);
     rank_inp.emit(s, Msg::RankInpChanged);
     let five_pin_chk = CheckButton::default()
@@ -341,11 +263,19 @@ fn main() {
         .with_size(wind.w() - 400, wind.h())
         .with_pos(180, 32);
     pack.set_spacing(8);
-    let mut mystery_box_inp = bounded_int_input("Mystery boxes bought", 0, 9999);
+    let mut mystery_box_inp = bounded_int_input(
+        "Mystery boxes bought",
+        0,
+        layout::MYSTERY_BOX_STATUS.max.into(),
+    );
     mystery_box_inp.set_size(0, 32);
     mystery_box_inp.emit(s, Msg::MysteryBoxInpChanged);
     mystery_box_inp.deactivate();
-    let mut abra_bead_inp = bounded_int_input("Abra bead capacity", 0, 255);
+    let mut abra_bead_inp = bounded_int_input(
+        "Abra bead capacity",
+        0,
+        layout::ABRA_BEAD_CAPACITY.max.into(),
+    );
     abra_bead_inp.set_size(0, 32);
     abra_bead_inp.emit(s, Msg::AbraBeadInpChanged);
     abra_bead_inp.deactivate();
@@ -429,17 +359,28 @@ fn main() {
                     {
                         player_data.chat_states[i] = ch.value() as u8;
                     }
-                    let pw = encode(&player_data);
-                    if mmpw_validate::validate_bin(&pw) {
-                        let pw = pretty_print_password(&pw, &name_inp.value());
-                        out.set_value(&pw);
-                    } else {
-                        println!(
-                            "Invalid pw: {:?} raw: {:?}",
-                            pretty_print_password(&pw, &name_inp.value()),
-                            &pw
-                        );
-                        out.set_value("[invalid password]");
+                    let pw = match mmpw_validate::encode(&player_data) {
+                        Ok(pw) => pw,
+                        Err(e) => {
+                            println!("Can't encode password: {}", e);
+                            out.set_value("[invalid password]");
+                            continue;
+                        }
+                    };
+                    match mmpw_validate::check_bin(&pw) {
+                        Ok(()) => {
+                            let pw = pretty_print_password(&pw, &name_inp.value());
+                            out.set_value(&pw);
+                        }
+                        Err(e) => {
+                            println!(
+                                "Invalid pw ({}): {:?}\n{}",
+                                e,
+                                pretty_print_password(&pw, &name_inp.value()),
+                                pw.explain()
+                            );
+                            out.set_value("[invalid password]");
+                        }
                     }
                 }
                 Msg::ItemClicked => {
@@ -477,16 +418,15 @@ fn main() {
 fn valid_name(name: String) -> String {
     name.to_ascii_uppercase()
         .chars()
-        .filter(|c| matches!(c, 'A'..='Z' | '.' | ' '))
+        .filter(|&c| binstring::is_name_char(c))
         .collect()
 }
 
 fn pretty_print_password(pw: &BinString, name: &str) -> String {
     let mut pw = pw.clone();
-    let key = mmpw_validate::binstring::hash_name(name.as_bytes());
+    let key = binstring::hash_name(name.as_bytes());
     pw.hash(&key);
-    let mut pw = pw.to_alphanumeric(18);
-    pw.insert(6, ' ');
-    pw.insert(13, ' ');
-    pw
+    let pw = pw.to_password();
+    let pw = std::str::from_utf8(&pw).unwrap();
+    format!("{} {} {}", &pw[..6], &pw[6..12], &pw[12..])
 }
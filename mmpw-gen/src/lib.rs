@@ -1,10 +1,26 @@
-use mmpw_validate::{binstring::BinString, validate, Password, LEN};
-use rand::{prelude::SliceRandom, thread_rng, Rng};
-use std::convert::TryInto;
+use mmpw_validate::{
+    alphabet,
+    binstring::{BinString, HashedKey},
+    partial::{self, Partial},
+    Password, LEN,
+};
+use rand::{prelude::SliceRandom, Rng};
+use std::{
+    collections::BTreeMap,
+    convert::TryInto,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc,
+    },
+    thread,
+};
 
 pub use data::{NAMES, SIX_LETTER_WORDS};
+pub use search::{CancelToken, Search, SearchResults, ValidPassword};
+pub use slice_permutations::{Checkpoint, CheckpointError, SlicePermutations};
 
 mod data;
+mod search;
 mod slice_permutations;
 
 pub type Word = [u8; 6];
@@ -17,47 +33,191 @@ fn fill_rand_words(buf: &mut Password, rng: &mut impl Rng, words: &[Word]) {
     }
 }
 
-pub fn permutate(
+pub fn permutate(key: &Key, words: &[Word], name: &str, f: impl FnMut(&Password, &str)) -> usize {
+    let start = SlicePermutations::<_, 2>::new(words).checkpoint();
+    permutate_from(key, words, name, &start, |_| {}, f).expect("checkpoint is for these words")
+}
+
+/// Like `permutate`, but only for the first two words `from` has left.
+///
+/// Make `from` with a `SlicePermutations<Word, 2>` over `words`, for example to
+/// search a shard. `save` is called with a checkpoint to resume from whenever a
+/// pair of first two words is done, after `f` got that pair's passwords.
+///
+/// Resuming from a checkpoint that wasn't saved after the last pair finds the
+/// passwords of the pairs since then again, so they may be passed to `f` twice.
+pub fn permutate_from(
     key: &Key,
     words: &[Word],
     name: &str,
+    from: &Checkpoint,
+    mut save: impl FnMut(&Checkpoint),
     mut f: impl FnMut(&Password, &str),
-) -> usize {
-    let mut s = [0; LEN as usize];
-    let permutations = slice_permutations::SlicePermutations::<_, 3>::new(words);
+) -> Result<usize, CheckpointError> {
+    let mut pairs = SlicePermutations::<_, 2>::resume(words, from)?;
+    let prepared = prepare_for_key(&HashedKey::new(key), words);
+    let thirds: Vec<PreparedWord> = prepared.iter().flatten().cloned().collect();
     let mut count = 0;
-    for [a, b, c] in permutations {
-        s[0..6].copy_from_slice(&a[..]);
-        s[6..12].copy_from_slice(&b[..]);
-        s[12..18].copy_from_slice(&c[..]);
-        if validate(&s, key) {
-            f(&s, name);
-            count += 1;
+    while let Some([a, b]) = pairs.next_indices() {
+        if let (Some(a), Some(b)) = (&prepared[a], &prepared[b]) {
+            permutate_prefix(a, b, &thirds, |pw| {
+                f(pw, name);
+                count += 1;
+            });
+        }
+        save(&pairs.checkpoint());
+    }
+    Ok(count)
+}
+
+/// Like `permutate`, but with about `words.len()²` work instead of `words.len()³`.
+///
+/// Third words are grouped by their checksum residue, so every pair of first two words
+/// only looks at the third words that complete a matching checksum. Finds the same
+/// passwords in the same order as `permutate`.
+pub fn solve(key: &Key, words: &[Word], name: &str, mut f: impl FnMut(&Password, &str)) -> usize {
+    let prepared: Vec<_> = prepare_for_key(&HashedKey::new(key), words)
+        .into_iter()
+        .flatten()
+        .collect();
+    let mut by_residue = vec![Vec::new(); partial::RESIDUES];
+    for c in &prepared {
+        by_residue[c.slots[2].residue() as usize].push(c);
+    }
+    let mut pw = [0; LEN as usize];
+    let mut count = 0;
+    for a in &prepared {
+        pw[0..6].copy_from_slice(&a.word[..]);
+        for b in &prepared {
+            pw[6..12].copy_from_slice(&b.word[..]);
+            let prefix = a.slots[0].join(&b.slots[1]);
+            for c in &by_residue[prefix.missing_residue() as usize] {
+                if prefix.join(&c.slots[2]).is_valid() {
+                    pw[12..18].copy_from_slice(&c.word[..]);
+                    f(&pw, name);
+                    count += 1;
+                }
+            }
         }
     }
     count
 }
 
-pub fn go_random(key: &Key, words: &[Word], name: &str) {
-    let mut s = [0; LEN as usize];
-    let mut rng = thread_rng();
-    loop {
-        fill_rand_words(&mut s, &mut rng, words);
-        if validate(&s, key) {
-            show(&s, name);
+/// A word with its unhashed bits and checksum share in each slot, for one key
+#[derive(Clone)]
+struct PreparedWord {
+    word: Word,
+    slots: [Partial; 3],
+}
+
+/// Prepare every word, or `None` for words with characters outside the alphabet,
+/// which are never part of a valid password
+fn prepare_for_key(key: &HashedKey, words: &[Word]) -> Vec<Option<PreparedWord>> {
+    words
+        .iter()
+        .map(|word| {
+            let slot = |pos| Partial::new(word, pos, key);
+            Some(PreparedWord {
+                word: *word,
+                slots: [slot(0)?, slot(6)?, slot(12)?],
+            })
+        })
+        .collect()
+}
+
+/// Call `f` with every valid password starting with `a` and `b`, in `permutate` order
+fn permutate_prefix(
+    a: &PreparedWord,
+    b: &PreparedWord,
+    prepared: &[PreparedWord],
+    mut f: impl FnMut(&Password),
+) {
+    let prefix = a.slots[0].join(&b.slots[1]);
+    let mut pw = [0; LEN as usize];
+    pw[0..6].copy_from_slice(&a.word[..]);
+    pw[6..12].copy_from_slice(&b.word[..]);
+    for c in prepared {
+        if prefix.join(&c.slots[2]).is_valid() {
+            pw[12..18].copy_from_slice(&c.word[..]);
+            f(&pw);
         }
     }
 }
 
-fn show(pw: &Password, name: &str) {
-    let utf = std::str::from_utf8(pw).unwrap();
-    println!(
-        "name: {} password: {} {} {}",
-        name,
-        &utf[0..6],
-        &utf[6..12],
-        &utf[12..]
-    );
+/// How far `permutate_parallel` got
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Progress {
+    /// Passwords checked so far
+    pub done: u64,
+    /// Passwords there are to check
+    pub total: u64,
+}
+
+/// Like `permutate`, but spread over `threads` threads.
+///
+/// Each thread takes the next word for the first slot and checks every password
+/// starting with it. `f` is still called on the calling thread, in the same order
+/// as `permutate` would call it. `progress` is called whenever a first word is done.
+pub fn permutate_parallel(
+    key: &Key,
+    words: &[Word],
+    name: &str,
+    threads: usize,
+    mut progress: impl FnMut(Progress),
+    mut f: impl FnMut(&Password, &str),
+) -> usize {
+    let prepared: Vec<_> = prepare_for_key(&HashedKey::new(key), words)
+        .into_iter()
+        .flatten()
+        .collect();
+    let next_first = AtomicUsize::new(0);
+    let per_first = (prepared.len() * prepared.len()) as u64;
+    let (sender, receiver) = mpsc::channel();
+    thread::scope(|scope| {
+        for _ in 0..threads.max(1) {
+            let sender = sender.clone();
+            let (prepared, next_first) = (&prepared, &next_first);
+            scope.spawn(move || loop {
+                let i = next_first.fetch_add(1, Ordering::Relaxed);
+                let a = match prepared.get(i) {
+                    Some(a) => a,
+                    None => return,
+                };
+                let mut found = Vec::new();
+                for b in prepared {
+                    permutate_prefix(a, b, prepared, |pw| found.push(*pw));
+                }
+                if sender.send((i, found)).is_err() {
+                    return;
+                }
+            });
+        }
+        drop(sender);
+        // Results of first words that finished before an earlier one did
+        let mut pending = BTreeMap::new();
+        let mut next_to_show = 0;
+        let mut count = 0;
+        for (done, (i, found)) in receiver.into_iter().enumerate() {
+            pending.insert(i, found);
+            while let Some(found) = pending.remove(&next_to_show) {
+                for pw in &found {
+                    f(pw, name);
+                }
+                count += found.len();
+                next_to_show += 1;
+            }
+            progress(Progress {
+                done: (done as u64 + 1) * per_first,
+                total: prepared.len() as u64 * per_first,
+            });
+        }
+        count
+    })
+}
+
+/// Random valid passwords, without end unless none of `words` can be typed
+pub fn go_random<'a>(key: &Key, words: &'a [Word], name: &'a str) -> SearchResults<'a> {
+    Search::new(key, words, name).random().into_iter()
 }
 
 #[derive(Debug)]
@@ -79,10 +239,10 @@ fn word_filter_map(word: &str) -> Result<[u8; 6], WordError> {
         Ok(bword) => {
             let mut bword: Word = bword;
             bword.make_ascii_uppercase();
-            if bword.contains(&b'V') {
-                return Err(WordError::InvalidChar(b'V'));
+            match bword.iter().find(|&&ch| alphabet::to_symbol(ch).is_none()) {
+                Some(&ch) => Err(WordError::InvalidChar(ch)),
+                None => Ok(bword),
             }
-            Ok(bword)
         }
         Err(_) => Err(WordError::InvalidLength),
     }
@@ -95,8 +255,88 @@ pub fn prepare_words<'a>(
     for word in words {
         match word_filter_map(word) {
             Ok(word) => vec.push(word),
-            Err(error) => return Err(WordPrepareError { word: word.into(), error }),
+            Err(error) => {
+                return Err(WordPrepareError {
+                    word: word.into(),
+                    error,
+                })
+            }
         }
     }
     Ok(vec)
 }
+
+/// The key for DEW, some words with one that can't be typed, and what `permutate`
+/// finds with them
+#[cfg(test)]
+fn dew_search() -> (Key, Vec<Word>, Vec<Password>) {
+    let key = mmpw_validate::binstring::hash_name(b"DEW");
+    // VULPIX can't be typed, and is skipped
+    let mut words = SIX_LETTER_WORDS[..40].to_vec();
+    words.push(*b"VULPIX");
+    let mut expected = Vec::new();
+    permutate(&key, &words, "DEW", |pw, _| expected.push(*pw));
+    (key, words, expected)
+}
+
+#[test]
+fn test_permutate() {
+    let key = mmpw_validate::binstring::hash_name(b"DEW");
+    let words = ["NEARBY", "SNOTTY", "SNEEZE", "ZIGZAG", "POTATO"];
+    let words = prepare_words(words.iter().copied()).unwrap();
+    let mut found = Vec::new();
+    let count = permutate(&key, &words, "DEW", |pw, _| found.push(*pw));
+    let key = mmpw_validate::binstring::HashedKey::new(&key);
+    let mut expected = Vec::new();
+    for a in &words {
+        for b in &words {
+            for c in &words {
+                let mut pw = [0; LEN as usize];
+                pw[0..6].copy_from_slice(a);
+                pw[6..12].copy_from_slice(b);
+                pw[12..18].copy_from_slice(c);
+                if mmpw_validate::validate_with(&pw, &key) {
+                    expected.push(pw);
+                }
+            }
+        }
+    }
+    assert_eq!(found, expected);
+    assert_eq!(count, expected.len());
+    assert!(found.contains(b"NEARBYSNOTTYSNEEZE"));
+}
+
+#[test]
+fn test_permutate_parallel() {
+    let (key, words, expected) = dew_search();
+    assert!(expected.len() > 1);
+    for &threads in [0, 1, 2, 3, 8].iter() {
+        let mut found = Vec::new();
+        let mut last = None;
+        let count = permutate_parallel(
+            &key,
+            &words,
+            "DEW",
+            threads,
+            |progress| last = Some(progress),
+            |pw, _| found.push(*pw),
+        );
+        assert_eq!(found, expected);
+        assert_eq!(count, expected.len());
+        let last = last.unwrap();
+        assert_eq!(last.done, last.total);
+    }
+}
+
+#[test]
+fn test_solve() {
+    let (key, words, expected) = dew_search();
+    assert!(expected.len() > 1);
+    let mut found = Vec::new();
+    assert_eq!(
+        solve(&key, &words, "DEW", |pw, _| found.push(*pw)),
+        expected.len()
+    );
+    assert_eq!(found, expected);
+    assert_eq!(solve(&key, &[], "DEW", |_, _| panic!()), 0);
+}
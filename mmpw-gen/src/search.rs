@@ -0,0 +1,258 @@
+use crate::{
+    fill_rand_words, permutate_prefix, prepare_for_key, Key, PreparedWord, SlicePermutations, Word,
+};
+use mmpw_validate::{binstring::HashedKey, validate_with, Password, LEN};
+use rand::{rngs::ThreadRng, thread_rng};
+use std::{
+    collections::VecDeque,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant},
+};
+
+/// How many random passwords are tried between checking whether to stop
+const RANDOM_TRIES_PER_CHECK: usize = 1024;
+
+/// A password found by a `Search`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidPassword<'a> {
+    pub password: Password,
+    /// Name the password is valid for
+    pub name: &'a str,
+}
+
+/// Stops the searches it's given to from another thread
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+/// Valid passwords made of three words, found one at a time.
+///
+/// By default the passwords come in the same order as from `permutate`.
+/// Iterating stops early once any of the limits is reached.
+#[derive(Debug, Clone)]
+pub struct Search<'a> {
+    key: HashedKey,
+    words: &'a [Word],
+    name: &'a str,
+    random: bool,
+    max_results: Option<usize>,
+    time_budget: Option<Duration>,
+    cancel: Option<CancelToken>,
+}
+
+impl<'a> Search<'a> {
+    pub fn new(key: &Key, words: &'a [Word], name: &'a str) -> Self {
+        Self {
+            key: HashedKey::new(key),
+            words,
+            name,
+            random: false,
+            max_results: None,
+            time_budget: None,
+            cancel: None,
+        }
+    }
+    /// Try random passwords instead, which never runs out of passwords to try unless
+    /// none of the words can be part of one
+    pub fn random(mut self) -> Self {
+        self.random = true;
+        self
+    }
+    /// Stop after finding `max_results` passwords
+    pub fn max_results(mut self, max_results: usize) -> Self {
+        self.max_results = Some(max_results);
+        self
+    }
+    /// Stop once the search has been going for `time_budget`, counted from the first
+    /// password asked for
+    pub fn time_budget(mut self, time_budget: Duration) -> Self {
+        self.time_budget = Some(time_budget);
+        self
+    }
+    /// Stop when `token` is cancelled
+    pub fn cancel_token(mut self, token: CancelToken) -> Self {
+        self.cancel = Some(token);
+        self
+    }
+}
+
+impl<'a> IntoIterator for Search<'a> {
+    type Item = ValidPassword<'a>;
+    type IntoIter = SearchResults<'a>;
+    fn into_iter(self) -> Self::IntoIter {
+        let prepared = prepare_for_key(&self.key, self.words);
+        let (source, stopped) = if self.random {
+            // Words that can't be typed are never part of a valid password
+            let words: Vec<Word> = prepared.into_iter().flatten().map(|p| p.word).collect();
+            // Random words can't be picked from nothing
+            let stopped = words.is_empty();
+            (Source::Random(thread_rng(), words), stopped)
+        } else {
+            let in_order = InOrder {
+                thirds: prepared.iter().flatten().cloned().collect(),
+                prepared,
+                pairs: SlicePermutations::new(self.words),
+                found: VecDeque::new(),
+            };
+            (Source::InOrder(in_order), false)
+        };
+        SearchResults {
+            stopped,
+            search: self,
+            source,
+            deadline: None,
+            found: 0,
+        }
+    }
+}
+
+/// Iterator over the passwords a `Search` finds
+pub struct SearchResults<'a> {
+    search: Search<'a>,
+    source: Source<'a>,
+    /// When the time budget runs out, set on the first call to `next`
+    deadline: Option<Instant>,
+    found: usize,
+    stopped: bool,
+}
+
+enum Source<'a> {
+    InOrder(InOrder<'a>),
+    /// Picks from the words that can be typed
+    Random(ThreadRng, Vec<Word>),
+}
+
+/// Where an in order search is, going through one pair of first two words at a time
+struct InOrder<'a> {
+    prepared: Vec<Option<PreparedWord>>,
+    thirds: Vec<PreparedWord>,
+    /// Pairs of first two words left to go through
+    pairs: SlicePermutations<'a, Word, 2>,
+    /// Passwords found with the last pair, not returned yet
+    found: VecDeque<Password>,
+}
+
+impl<'a> InOrder<'a> {
+    /// Look for the next password, stopping after at most one pair of first two words.
+    ///
+    /// Returns `None` when there is nothing left to look through.
+    fn step(&mut self) -> Option<Option<Password>> {
+        if let Some(pw) = self.found.pop_front() {
+            return Some(Some(pw));
+        }
+        let [a, b] = self.pairs.next_indices()?;
+        if let (Some(a), Some(b)) = (&self.prepared[a], &self.prepared[b]) {
+            let found = &mut self.found;
+            permutate_prefix(a, b, &self.thirds, |pw| found.push_back(*pw));
+        }
+        Some(None)
+    }
+}
+
+impl<'a> SearchResults<'a> {
+    fn limit_reached(&self) -> bool {
+        let search = &self.search;
+        search.max_results.is_some_and(|max| self.found >= max)
+            || search
+                .cancel
+                .as_ref()
+                .is_some_and(CancelToken::is_cancelled)
+            || self
+                .deadline
+                .is_some_and(|deadline| Instant::now() >= deadline)
+    }
+}
+
+impl<'a> Iterator for SearchResults<'a> {
+    type Item = ValidPassword<'a>;
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.deadline.is_none() {
+            // Only ask for the time when needed, it's not available everywhere
+            self.deadline = self
+                .search
+                .time_budget
+                .map(|budget| Instant::now() + budget);
+        }
+        loop {
+            if self.stopped || self.limit_reached() {
+                self.stopped = true;
+                return None;
+            }
+            let search = &self.search;
+            let found = match &mut self.source {
+                Source::InOrder(in_order) => match in_order.step() {
+                    Some(found) => found,
+                    None => {
+                        self.stopped = true;
+                        return None;
+                    }
+                },
+                Source::Random(rng, words) => {
+                    let mut pw = [0; LEN as usize];
+                    (0..RANDOM_TRIES_PER_CHECK).find_map(|_| {
+                        fill_rand_words(&mut pw, rng, words);
+                        Some(pw).filter(|pw| validate_with(pw, &search.key))
+                    })
+                }
+            };
+            if let Some(password) = found {
+                self.found += 1;
+                return Some(ValidPassword {
+                    password,
+                    name: self.search.name,
+                });
+            }
+        }
+    }
+}
+
+#[test]
+fn test_search() {
+    use crate::SIX_LETTER_WORDS;
+
+    let (key, words, expected) = crate::dew_search();
+    assert!(expected.len() > 3);
+    let search = Search::new(&key, &words, "DEW");
+    let passwords = |search: Search| -> Vec<Password> {
+        search.into_iter().map(|found| found.password).collect()
+    };
+    assert_eq!(passwords(search.clone()), expected);
+    assert!(search.clone().into_iter().all(|found| found.name == "DEW"));
+    assert_eq!(passwords(search.clone().max_results(3)), &expected[..3]);
+    assert!(passwords(search.clone().max_results(0)).is_empty());
+    assert!(passwords(Search::new(&key, &[], "DEW")).is_empty());
+    assert!(passwords(Search::new(&key, &[], "DEW").random()).is_empty());
+    let untypable = [*b"VULPIX", *b"VANITY"];
+    assert!(passwords(Search::new(&key, &untypable, "DEW").random()).is_empty());
+
+    assert!(passwords(search.clone().time_budget(Duration::from_secs(0))).is_empty());
+    let long = search.clone().time_budget(Duration::from_secs(3600));
+    assert_eq!(passwords(long), expected);
+    let random = Search::new(&key, &SIX_LETTER_WORDS, "DEW").random();
+    let budget = random.clone().time_budget(Duration::from_millis(50));
+    assert!(budget
+        .into_iter()
+        .all(|found| mmpw_validate::validate(&found.password, &key)));
+
+    let token = CancelToken::new();
+    let mut results = random.cancel_token(token.clone()).into_iter();
+    assert!(results.next().is_some());
+    token.cancel();
+    assert_eq!(results.next(), None);
+    assert_eq!(results.next(), None);
+    assert!(passwords(search.cancel_token(token)).is_empty());
+}
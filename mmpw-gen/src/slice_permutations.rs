@@ -1,15 +1,142 @@
+use std::{convert::TryFrom, fmt, iter::FusedIterator};
+
+/// Every way to fill `SLOTS` slots with items of a slice, the last slot changing fastest.
+///
+/// The permutations are numbered in that order, so going through them can be split
+/// into shards, stopped, and resumed from a `Checkpoint`.
+#[derive(Debug, Clone)]
 pub struct SlicePermutations<'a, T, const SLOTS: usize> {
     slice: &'a [T],
-    indices: [usize; SLOTS],
-    first: bool,
+    /// Index of the next permutation
+    position: u64,
+    /// Index after the last permutation to go through
+    end: u64,
+    /// Fingerprint of `slice`, if it's known already
+    fingerprint: Option<u64>,
 }
 
 impl<'a, T, const SLOTS: usize> SlicePermutations<'a, T, SLOTS> {
     pub fn new(slice: &'a [T]) -> Self {
         Self {
             slice,
-            indices: [0; SLOTS],
-            first: true,
+            position: 0,
+            end: Self::total_for(slice.len()),
+            fingerprint: None,
+        }
+    }
+    fn total_for(len: usize) -> u64 {
+        (len as u64)
+            .checked_pow(SLOTS as u32)
+            .expect("too many permutations to count")
+    }
+    /// Number of permutations of the whole slice, not just this shard
+    pub fn total(&self) -> u64 {
+        Self::total_for(self.slice.len())
+    }
+    /// Index of the next permutation
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+    /// Number of permutations left
+    pub fn len(&self) -> u64 {
+        self.end - self.position
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// Continue with the permutation at `position`, or stop if it's past the end
+    pub fn seek(&mut self, position: u64) {
+        self.position = position.min(self.end);
+    }
+    /// Part `index` of `count` disjoint parts of about the same size that the
+    /// remaining permutations can be split into.
+    ///
+    /// # Panics
+    ///
+    /// If `index` is not less than `count`.
+    pub fn shard(&self, index: u64, count: u64) -> Self {
+        assert!(index < count, "shard {} of {} doesn't exist", index, count);
+        let len = u128::from(self.end - self.position);
+        let offset = |i: u64| self.position + (len * u128::from(i) / u128::from(count)) as u64;
+        Self {
+            slice: self.slice,
+            position: offset(index),
+            end: offset(index + 1),
+            fingerprint: self.fingerprint,
+        }
+    }
+    /// Indices into the slice of the items in each slot of the next permutation,
+    /// going past it
+    pub(crate) fn next_indices(&mut self) -> Option<[usize; SLOTS]> {
+        if self.position >= self.end {
+            return None;
+        }
+        let len = self.slice.len() as u64;
+        let mut rest = self.position;
+        let mut indices = [0; SLOTS];
+        for slot in indices.iter_mut().rev() {
+            *slot = (rest % len) as usize;
+            rest /= len;
+        }
+        self.position += 1;
+        Some(indices)
+    }
+}
+
+impl<'a, T: AsRef<[u8]>, const SLOTS: usize> SlicePermutations<'a, T, SLOTS> {
+    /// Where to resume from to go through the remaining permutations
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint {
+            slots: SLOTS,
+            len: self.slice.len(),
+            fingerprint: self.fingerprint.unwrap_or_else(|| fingerprint(self.slice)),
+            position: self.position,
+            end: self.end,
+        }
+    }
+    /// Go through the permutations `checkpoint` has left, which must be from
+    /// permutations of the same slice
+    pub fn resume(slice: &'a [T], checkpoint: &Checkpoint) -> Result<Self, CheckpointError> {
+        if checkpoint.slots != SLOTS || checkpoint.len != slice.len() {
+            return Err(CheckpointError::Mismatch);
+        }
+        let resumed = Self {
+            slice,
+            position: checkpoint.position,
+            end: checkpoint.end,
+            fingerprint: Some(fingerprint(slice)),
+        };
+        if resumed.fingerprint != Some(checkpoint.fingerprint) {
+            return Err(CheckpointError::Mismatch);
+        }
+        if resumed.position > resumed.end || resumed.end > resumed.total() {
+            return Err(CheckpointError::OutOfRange);
+        }
+        Ok(resumed)
+    }
+}
+
+/// A hash of `slice` that stays the same between runs, machines and Rust versions.
+///
+/// FNV-1a over the number of items, then the length and bytes of each item, with
+/// lengths as little-endian `u64`s.
+fn fingerprint<T: AsRef<[u8]>>(slice: &[T]) -> u64 {
+    let mut hasher = Fnv1a(0xcbf2_9ce4_8422_2325);
+    hasher.write(&(slice.len() as u64).to_le_bytes());
+    for item in slice {
+        let bytes = item.as_ref();
+        hasher.write(&(bytes.len() as u64).to_le_bytes());
+        hasher.write(bytes);
+    }
+    hasher.0
+}
+
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 = (self.0 ^ u64::from(byte)).wrapping_mul(0x100_0000_01b3);
         }
     }
 }
@@ -17,29 +144,166 @@ impl<'a, T, const SLOTS: usize> SlicePermutations<'a, T, SLOTS> {
 impl<'a, T, const SLOTS: usize> Iterator for SlicePermutations<'a, T, SLOTS> {
     type Item = [&'a T; SLOTS];
     fn next(&mut self) -> Option<Self::Item> {
-        if self.slice.is_empty() {
-            return None;
+        let slice = self.slice;
+        self.next_indices()
+            .map(|indices| indices.map(|index| &slice[index]))
+    }
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match usize::try_from(self.len()) {
+            Ok(len) => (len, Some(len)),
+            Err(_) => (usize::MAX, None),
         }
-        let mut arr = [&self.slice[0]; SLOTS];
-        if self.first {
-            self.first = false;
-            return Some(arr);
-        }
-        let mut i = SLOTS - 1;
-        loop {
-            if self.indices[i] < self.slice.len() - 1 {
-                self.indices[i] += 1;
-                for (j, &indice) in self.indices.iter().enumerate() {
-                    arr[j] = &self.slice[indice];
-                }
-                return Some(arr);
-            } else {
-                self.indices[i] = 0;
-                if i == 0 {
-                    return None;
-                }
-                i -= 1;
-            }
+    }
+    fn nth(&mut self, n: usize) -> Option<Self::Item> {
+        self.seek(self.position.saturating_add(n as u64));
+        self.next()
+    }
+}
+
+impl<'a, T, const SLOTS: usize> FusedIterator for SlicePermutations<'a, T, SLOTS> {}
+
+/// How far through its permutations a `SlicePermutations` got
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Checkpoint {
+    slots: usize,
+    len: usize,
+    /// Fingerprint of the slice
+    fingerprint: u64,
+    position: u64,
+    end: u64,
+}
+
+impl Checkpoint {
+    /// Index of the next permutation
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+    /// Index after the last permutation to go through
+    pub fn end(&self) -> u64 {
+        self.end
+    }
+    pub fn is_done(&self) -> bool {
+        self.position >= self.end
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum CheckpointError {
+    /// Checkpoint is for a different number of slots or a different slice
+    Mismatch,
+    /// Checkpoint position or end is past the last permutation
+    OutOfRange,
+}
+
+impl fmt::Display for CheckpointError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Mismatch => f.write_str("checkpoint is for a different word list"),
+            Self::OutOfRange => f.write_str("checkpoint position is out of range"),
         }
     }
 }
+
+impl std::error::Error for CheckpointError {}
+
+#[test]
+fn test_shard() {
+    let slice = [1, 2, 3, 4, 5];
+    let all: Vec<_> = SlicePermutations::<_, 3>::new(&slice).collect();
+    assert_eq!(all.len(), 125);
+    assert_eq!(&all[..2], &[[&1, &1, &1], [&1, &1, &2]]);
+    for &count in [1, 2, 3, 7, 125, 200].iter() {
+        let mut joined = Vec::new();
+        for index in 0..count {
+            let shard = SlicePermutations::<_, 3>::new(&slice).shard(index, count);
+            assert_eq!(shard.len() as usize, shard.clone().count());
+            joined.extend(shard);
+        }
+        // In order, without gaps or overlaps
+        assert_eq!(joined, all);
+    }
+    // Shards of what's left after seeking
+    let mut rest = SlicePermutations::<_, 3>::new(&slice);
+    rest.seek(100);
+    let halves: Vec<_> = (0..2).flat_map(|i| rest.shard(i, 2)).collect();
+    assert_eq!(halves, &all[100..]);
+}
+
+#[test]
+fn test_seek() {
+    let slice = ['a', 'b', 'c'];
+    let perms = SlicePermutations::<_, 3>::new(&slice);
+    let mut stepped = perms.clone();
+    for n in 0..=28 {
+        let mut sought = perms.clone();
+        sought.seek(n);
+        assert_eq!(sought.position(), stepped.position());
+        assert_eq!(sought.len(), stepped.len());
+        assert_eq!(sought.size_hint(), stepped.size_hint());
+        assert_eq!(perms.clone().nth(n as usize), stepped.clone().next());
+        assert!(sought.eq(stepped.clone()));
+        stepped.next();
+    }
+    assert!(stepped.is_empty());
+    assert_eq!(stepped.next(), None);
+}
+
+#[test]
+fn test_checkpoint() {
+    let slice = [*b"NEARBY", *b"SNOTTY", *b"SNEEZE"];
+    let mut perms = SlicePermutations::<_, 2>::new(&slice).shard(1, 2);
+    perms.next();
+    let checkpoint = perms.checkpoint();
+    assert_eq!(
+        (checkpoint.position(), checkpoint.end()),
+        (perms.position(), 9)
+    );
+    let resumed = SlicePermutations::<_, 2>::resume(&slice, &checkpoint).unwrap();
+    assert!(resumed.clone().eq(perms.clone()));
+    assert_eq!(resumed.checkpoint(), checkpoint);
+    #[cfg(feature = "serde")]
+    {
+        let json = serde_json::to_string(&checkpoint).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Checkpoint>(&json).unwrap(),
+            checkpoint
+        );
+    }
+
+    let mismatch = |slice: &[[u8; 6]]| SlicePermutations::<_, 2>::resume(slice, &checkpoint).err();
+    let changed = [*b"NEARBY", *b"SNOTTY", *b"SNEEZL"];
+    assert_eq!(mismatch(&changed), Some(CheckpointError::Mismatch));
+    assert_eq!(mismatch(&slice[..2]), Some(CheckpointError::Mismatch));
+    assert_eq!(
+        SlicePermutations::<_, 3>::resume(&slice, &checkpoint).err(),
+        Some(CheckpointError::Mismatch)
+    );
+    for &(position, end) in [(8, 7), (9, 10)].iter() {
+        let out_of_range = Checkpoint {
+            position,
+            end,
+            ..checkpoint
+        };
+        assert_eq!(
+            SlicePermutations::<_, 2>::resume(&slice, &out_of_range).err(),
+            Some(CheckpointError::OutOfRange)
+        );
+    }
+    perms.by_ref().for_each(drop);
+    assert!(perms.checkpoint().is_done());
+}
+
+#[test]
+fn test_fingerprint() {
+    // Saved checkpoints rely on these never changing
+    assert_eq!(fingerprint::<&[u8]>(&[]), 0xa8c7_f832_281a_39c5);
+    assert_eq!(
+        fingerprint(&[*b"NEARBY", *b"SNOTTY"]),
+        0x54f1_c138_506d_69c5
+    );
+    assert_ne!(
+        fingerprint(&[&b"AB"[..], b"C"]),
+        fingerprint(&[&b"A"[..], b"BC"])
+    );
+}
@@ -1,12 +1,52 @@
-use criterion::{criterion_group, criterion_main, Criterion};
-use mmpw_validate::{binstring, validate};
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use mmpw_validate::{
+    binstring::{self, BinString, HashedKey},
+    validate, validate_batch, validate_with, Password,
+};
 
 fn bench_validate(c: &mut Criterion) {
     let key = binstring::hash_name(b"Dew");
     c.bench_function("validate", |b| {
         b.iter(|| validate(b"MYTHICDREAMYDEFECT", &key))
     });
+    let hashed_key = HashedKey::new(&key);
+    c.bench_function("validate_with", |b| {
+        b.iter(|| validate_with(b"MYTHICDREAMYDEFECT", &hashed_key))
+    });
+}
+
+fn bench_validate_batch(c: &mut Criterion) {
+    let hashed_key = HashedKey::new(&binstring::hash_name(b"Dew"));
+    let pws: Vec<Password> = (0..1024)
+        .map(|i| {
+            let mut pw = *b"MYTHICDREAMYDEFECT";
+            pw[17] = mmpw_validate::alphabet::from_symbol(i as u8);
+            pw[16] = mmpw_validate::alphabet::from_symbol((i >> 5) as u8);
+            pw
+        })
+        .collect();
+    let mut valid = vec![false; pws.len()];
+    c.bench_function("validate_with 1024", |b| {
+        b.iter(|| {
+            for (pw, valid) in pws.iter().zip(valid.iter_mut()) {
+                *valid = validate_with(pw, &hashed_key);
+            }
+        })
+    });
+    c.bench_function("validate_batch 1024", |b| {
+        b.iter(|| validate_batch(&pws, &hashed_key, &mut valid))
+    });
+}
+
+fn bench_to_password(c: &mut Criterion) {
+    let bs = BinString::from_alphanumeric(b"MYTHICDREAMYDEFECT");
+    c.bench_function("to_password", |b| b.iter(|| black_box(&bs).to_password()));
 }
 
-criterion_group!(benches, bench_validate);
+criterion_group!(
+    benches,
+    bench_validate,
+    bench_validate_batch,
+    bench_to_password
+);
 criterion_main!(benches);
@@ -0,0 +1,98 @@
+//! The 32 characters a password can be made of.
+//!
+//! Each character stands for a 5 bit symbol. The alphabet is the uppercase letters
+//! except `V`, and the digits `3` to `9`.
+
+/// Characters of the alphabet, indexed by their symbol
+pub const CHARSET: [u8; 32] = *b"3HGFR68IQWJ5XTKZAY7O94PDUCESMNBL";
+
+/// Characters that are easily mistaken for each other.
+///
+/// Some of them aren't part of the alphabet, but are still typed by accident.
+pub const CONFUSABLES: [(u8, u8); 12] = [
+    (b'0', b'O'),
+    (b'0', b'D'),
+    (b'O', b'D'),
+    (b'O', b'Q'),
+    (b'1', b'I'),
+    (b'1', b'L'),
+    (b'I', b'L'),
+    (b'2', b'Z'),
+    (b'V', b'U'),
+    (b'8', b'B'),
+    (b'5', b'S'),
+    (b'6', b'G'),
+];
+
+pub(crate) const NOT_IN_CHARSET: u8 = 0xFF;
+/// Symbol of every byte value, `NOT_IN_CHARSET` for bytes outside the alphabet
+pub(crate) static SYMBOLS: [u8; 256] = symbols();
+
+const fn symbols() -> [u8; 256] {
+    let mut table = [NOT_IN_CHARSET; 256];
+    let mut sym = 0;
+    while sym < CHARSET.len() {
+        table[CHARSET[sym] as usize] = sym as u8;
+        sym += 1;
+    }
+    table
+}
+
+/// The symbol a character stands for, or `None` if it's not in the alphabet
+pub fn to_symbol(ch: u8) -> Option<u8> {
+    match SYMBOLS[ch as usize] {
+        NOT_IN_CHARSET => None,
+        sym => Some(sym),
+    }
+}
+
+/// The character standing for `sym`. Only the lowest 5 bits are used.
+pub fn from_symbol(sym: u8) -> u8 {
+    CHARSET[(sym & 0b11111) as usize]
+}
+
+pub fn is_valid_char(ch: char) -> bool {
+    ch.is_ascii() && to_symbol(ch as u8).is_some()
+}
+
+/// Every character of the alphabet, in symbol order
+pub fn charset() -> impl Iterator<Item = u8> {
+    CHARSET.iter().copied()
+}
+
+/// Characters of the alphabet that `ch` could have been mistaken for
+pub fn confusables(ch: u8) -> impl Iterator<Item = u8> {
+    CONFUSABLES
+        .iter()
+        .filter_map(move |&(a, b)| match ch {
+            _ if ch == a => Some(b),
+            _ if ch == b => Some(a),
+            _ => None,
+        })
+        .filter(|&other| to_symbol(other).is_some())
+}
+
+#[test]
+fn test_symbols() {
+    for (sym, ch) in charset().enumerate() {
+        assert_eq!(to_symbol(ch), Some(sym as u8));
+        assert_eq!(from_symbol(sym as u8), ch);
+    }
+    assert_eq!(to_symbol(b'3'), Some(0b00000));
+    assert_eq!(to_symbol(b'L'), Some(0b11111));
+    for &ch in b"0129Vv a-".iter() {
+        assert_eq!(to_symbol(ch).is_some(), ch == b'9');
+    }
+    assert_eq!(charset().filter(u8::is_ascii_digit).count(), 7);
+    assert!(is_valid_char('O') && is_valid_char('I'));
+    assert!(!is_valid_char('Ö') && !is_valid_char('o'));
+}
+
+#[test]
+fn test_confusables() {
+    assert!(confusables(b'0').eq(b"OD".iter().copied()));
+    assert!(confusables(b'O').eq(b"DQ".iter().copied()));
+    assert!(confusables(b'V').eq(b"U".iter().copied()));
+    assert!(confusables(b'U').next().is_none());
+    assert!(confusables(b'A').next().is_none());
+}
@@ -1,4 +1,4 @@
-use std::ops::{Deref, DerefMut, Index, IndexMut};
+use core::ops::{Deref, DerefMut, Index, IndexMut};
 
 #[derive(Debug, PartialEq, Clone)]
 pub struct ArrayByteVec<const N: usize> {
@@ -10,19 +10,6 @@ impl<const N: usize> ArrayByteVec<N> {
     pub fn zeroed_with_len(len: usize) -> Self {
         Self { array: [0; N], len }
     }
-    pub fn from_raw(raw: [u8; N], len: usize) -> Self {
-        Self { array: raw, len }
-    }
-    pub fn insert(&mut self, index: usize, value: u8) {
-        assert!(self.len < N);
-        // Shift all items by 1
-        for i in (index..N).rev() {
-            let v = if i > 0 { self.array[i - 1] } else { 0 };
-            self.array[i] = v;
-        }
-        self.array[index] = value;
-        self.len += 1;
-    }
 }
 
 impl<const N: usize> Index<u8> for ArrayByteVec<N> {
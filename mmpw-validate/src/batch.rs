@@ -0,0 +1,243 @@
+//! Validation of many passwords at once.
+//!
+//! The bits of 64 passwords are transposed into 90 words, one for each bit position,
+//! with bit `63 - n` of each word belonging to the `n`th password. Every check is then
+//! done with plain integer operations on all passwords in parallel.
+//!
+//! This is for checking arbitrary lists of candidates. Searches that put passwords
+//! together from the same few words, like `permutate` in mmpw-gen, are faster with
+//! the prepared parts in `partial`.
+
+use crate::{
+    alphabet,
+    binstring::{BinString, HashedKey, CKSUM_INTS, UNSHUFFLE_90},
+    layout::{self, Field, FieldId},
+    Password, CKSUM_BITS, LEN,
+};
+use core::convert::TryInto;
+
+/// How many passwords are checked in one pass
+pub const BATCH_LEN: usize = 64;
+
+const BIT_LEN: usize = LEN as usize * 5;
+const CKSUM_WEIGHTS: usize = CKSUM_BITS as usize;
+pub(crate) const DIGITS_TO_READ: usize = BIT_LEN - CKSUM_WEIGHTS;
+
+/// Checksum of the all zero bit string
+pub(crate) const CKSUM_BASE: u32 = cksum_base();
+/// Bits that add `2^weight` to the checksum, for each weight
+static CKSUM_COLUMNS: [Column; CKSUM_WEIGHTS] = cksum_columns();
+
+struct Column {
+    bits: [u8; DIGITS_TO_READ],
+    len: usize,
+}
+
+const fn cksum_base() -> u32 {
+    let mut sum = CKSUM_INTS[0];
+    let mut i = 0;
+    while i < DIGITS_TO_READ {
+        sum = sum.wrapping_add(CKSUM_INTS[i]);
+        i += 1;
+    }
+    sum
+}
+
+const fn cksum_columns() -> [Column; CKSUM_WEIGHTS] {
+    const EMPTY: Column = Column {
+        bits: [0; DIGITS_TO_READ],
+        len: 0,
+    };
+    let mut columns = [EMPTY; CKSUM_WEIGHTS];
+    let mut i = 0;
+    while i < DIGITS_TO_READ {
+        // How much the checksum changes when the bit is set instead of unset
+        let delta = CKSUM_INTS[i + 17].wrapping_sub(CKSUM_INTS[i]);
+        let mut weight = 0;
+        while weight < CKSUM_WEIGHTS {
+            if delta >> weight & 1 == 1 {
+                let column = &mut columns[weight];
+                column.bits[column.len] = i as u8;
+                column.len += 1;
+            }
+            weight += 1;
+        }
+        i += 1;
+    }
+    columns
+}
+
+/// Like `validate_with` for every password in `pws`, storing the results in `valid`.
+///
+/// # Panics
+///
+/// If `pws` and `valid` have different lengths.
+pub fn validate_batch(pws: &[Password], key: &HashedKey, valid: &mut [bool]) {
+    assert_eq!(pws.len(), valid.len());
+    let mut key_bits = BinString::zeroed();
+    key_bits.hash_with(key);
+    let key_bits = key_bits.as_u128();
+    let mut key_planes = [0; BIT_LEN];
+    for (i, plane) in key_planes.iter_mut().enumerate() {
+        *plane = ((key_bits >> (127 - i)) as u64 & 1).wrapping_neg();
+    }
+    for (pws, valid) in pws.chunks(BATCH_LEN).zip(valid.chunks_mut(BATCH_LEN)) {
+        let good = validate_chunk(pws, &key_planes);
+        for (lane, valid) in valid.iter_mut().enumerate() {
+            *valid = good >> (63 - lane) & 1 == 1;
+        }
+    }
+}
+
+/// Lanes of the up to 64 passwords in `pws` that are valid
+fn validate_chunk(pws: &[Password], key_planes: &[u64; BIT_LEN]) -> u64 {
+    // Symbols of each password in order, as they are before unshuffling.
+    // The first 64 bits go in `hi` and the rest in `lo`.
+    let mut rows = [0; 128];
+    let mut bad = 0;
+    for (lane, pw) in pws.iter().enumerate() {
+        // The first 12 symbols fill 60 bits, the last 6 fill 30
+        let (mut first, mut second) = (0u64, 0u64);
+        let mut invalid = 0;
+        for &ch in pw[..12].iter() {
+            let sym = alphabet::SYMBOLS[ch as usize];
+            invalid |= sym;
+            first = first << 5 | u64::from(sym & 0b11111);
+        }
+        for &ch in pw[12..].iter() {
+            let sym = alphabet::SYMBOLS[ch as usize];
+            invalid |= sym;
+            second = second << 5 | u64::from(sym & 0b11111);
+        }
+        rows[lane] = first << 4 | second >> 26;
+        rows[64 + lane] = second << 38;
+        bad |= u64::from(invalid == alphabet::NOT_IN_CHARSET) << (63 - lane);
+    }
+    let (hi, lo) = rows.split_at_mut(64);
+    transpose(hi.try_into().unwrap());
+    transpose(lo.try_into().unwrap());
+    let mut planes = [0; BIT_LEN];
+    for ((plane, &src), &key) in planes.iter_mut().zip(UNSHUFFLE_90.iter()).zip(key_planes) {
+        *plane = rows[src] ^ key;
+    }
+
+    let all_items = planes[0];
+    bad |= all_items & out_of_range(&planes, &layout::CHECKED[1]);
+    bad |= !all_items & out_of_range(&planes, &layout::CHECKED[0]);
+    bad |= checksum_mismatch(&planes);
+    let unused_lanes = !0u64.checked_shr(pws.len() as u32).unwrap_or(0);
+    !bad & unused_lanes
+}
+
+/// Transpose a 64x64 bit matrix, with the first column in the most significant bit
+fn transpose(rows: &mut [u64; 64]) {
+    let mut width = 32;
+    let mut mask: u64 = 0x0000_0000_FFFF_FFFF;
+    while width != 0 {
+        let mut k = 0;
+        while k < 64 {
+            let t = (rows[k] ^ rows[k + width] >> width) & mask;
+            rows[k] ^= t;
+            rows[k + width] ^= t << width;
+            k = (k + width + 1) & !width;
+        }
+        width >>= 1;
+        mask ^= mask << width;
+    }
+}
+
+/// Lanes where a checked field, other than the checksum, is out of range
+fn out_of_range(planes: &[u64; BIT_LEN], checked: &layout::Checked) -> u64 {
+    let mut bad = 0;
+    for (offset, field) in checked.fields() {
+        if field.id != FieldId::Checksum {
+            bad |= field_out_of_range(&planes[*offset..][..field.width as usize], field);
+        }
+    }
+    bad
+}
+
+fn field_out_of_range(planes: &[u64], field: &Field) -> u64 {
+    let (mut less, mut greater) = (0, 0);
+    let (mut eq_min, mut eq_max) = (!0, !0);
+    for (i, &plane) in planes.iter().enumerate() {
+        let shift = planes.len() - 1 - i;
+        if field.min >> shift & 1 == 1 {
+            less |= eq_min & !plane;
+            eq_min &= plane;
+        } else {
+            eq_min &= !plane;
+        }
+        if field.max >> shift & 1 == 1 {
+            eq_max &= plane;
+        } else {
+            greater |= eq_max & plane;
+            eq_max &= !plane;
+        }
+    }
+    less | greater
+}
+
+/// Lanes where the stored checksum doesn't match the calculated one.
+///
+/// The bits of each weight are summed with full adders, lowest weight first,
+/// and the carries are added to the next weight.
+fn checksum_mismatch(planes: &[u64; BIT_LEN]) -> u64 {
+    let stored = &planes[DIGITS_TO_READ..];
+    // Bits of the current weight, followed by the carries from the previous one
+    let mut inputs = [0; 2 * DIGITS_TO_READ + 1];
+    let mut n_carries = 0;
+    let mut bad = 0;
+    for (weight, column) in CKSUM_COLUMNS.iter().enumerate() {
+        let bits = &column.bits[..column.len];
+        let mut carries = [0; DIGITS_TO_READ];
+        inputs.copy_within(..n_carries, bits.len());
+        for (input, &bit) in inputs.iter_mut().zip(bits) {
+            *input = planes[bit as usize];
+        }
+        let len = bits.len() + n_carries;
+        inputs[len] = 0;
+        let mut sum = if CKSUM_BASE >> weight & 1 == 1 { !0 } else { 0 };
+        n_carries = 0;
+        for pair in inputs[..len + 1].chunks_exact(2) {
+            let (a, b) = (pair[0], pair[1]);
+            carries[n_carries] = sum & a | b & (sum ^ a);
+            sum ^= a ^ b;
+            n_carries += 1;
+        }
+        bad |= sum ^ stored[CKSUM_WEIGHTS - 1 - weight];
+        inputs[..n_carries].copy_from_slice(&carries[..n_carries]);
+    }
+    bad
+}
+
+#[test]
+fn test_validate_batch() {
+    use crate::binstring::hash_name;
+
+    let key = HashedKey::new(&hash_name(b"DEW"));
+    let mut pws = [*b"NEARBYSNOTTYSNEEZE"; 150];
+    pws[1] = *b"NICBRBINC8O4PKGOZK";
+    pws[2] = *b"88H4B75X8FR9C54577";
+    pws[3] = *b"NEARBYSN0TTYSNEEZE";
+    // Vary every character, so every bit gets tested valid and invalid
+    for (pw, random) in pws[4..].iter_mut().zip(crate::random_passwords()) {
+        *pw = random;
+    }
+    // Make some all items passwords, with and without a good sentinel
+    let mut data = crate::decode(b"NEARBYSNOTTYSNEEZE", &hash_name(b"DEW")).unwrap();
+    data.items = [true; crate::player_data::ITEM_COUNT];
+    let mut bs = crate::encode(&data).unwrap();
+    bs.hash_with(&key);
+    pws[100] = bs.to_password();
+    pws[101] = pws[100];
+    pws[101][5] = b'3';
+    let mut valid = [false; 150];
+    validate_batch(&pws, &key, &mut valid);
+    for (pw, &valid) in pws.iter().zip(valid.iter()) {
+        assert_eq!(valid, crate::validate_with(pw, &key), "{:?}", pw);
+    }
+    assert!(valid[0] && valid[1] && !valid[2] && !valid[3] && valid[100]);
+    assert!(valid.iter().filter(|&&v| !v).count() > 50);
+    validate_batch(&[], &key, &mut []);
+}
@@ -1,45 +1,13 @@
+use crate::alphabet;
 use crate::array_byte_vec::ArrayByteVec;
-use std::io::Write;
-
-const fn alnum_to_bin(alnum: u8) -> u8 {
-    match alnum {
-        b'3' => 0b00000,
-        b'H' => 0b00001,
-        b'G' => 0b00010,
-        b'F' => 0b00011,
-        b'R' => 0b00100,
-        b'6' => 0b00101,
-        b'8' => 0b00110,
-        b'I' => 0b00111,
-        b'Q' => 0b01000,
-        b'W' => 0b01001,
-        b'J' => 0b01010,
-        b'5' => 0b01011,
-        b'X' => 0b01100,
-        b'T' => 0b01101,
-        b'K' => 0b01110,
-        b'Z' => 0b01111,
-        b'A' => 0b10000,
-        b'Y' => 0b10001,
-        b'7' => 0b10010,
-        b'O' => 0b10011,
-        b'9' => 0b10100,
-        b'4' => 0b10101,
-        b'P' => 0b10110,
-        b'D' => 0b10111,
-        b'U' => 0b11000,
-        b'C' => 0b11001,
-        b'E' => 0b11010,
-        b'S' => 0b11011,
-        b'M' => 0b11100,
-        b'N' => 0b11101,
-        b'B' => 0b11110,
-        b'L' => 0b11111,
-        _ => 0b00000,
-    }
-}
-
-const CKSUM_INTS: [u32; 100] = [
+use crate::layout::{self, FieldId};
+use core::fmt::{self, Write};
+
+fn alnum_to_bin(alnum: u8) -> u8 {
+    alphabet::to_symbol(alnum).unwrap_or(0b00000)
+}
+
+pub(crate) const CKSUM_INTS: [u32; 100] = [
     608356525, 403119806, 600082856, 501903605, 395995676, 639983625, 520697153, 373011710,
     481025613, 609081731, 423005485, 362660979, 736545212, 334902753, 172235946, 995274085,
     518629176, 285694472, 421177161, 558665377, 324282222, 360158599, 878917102, 868418206,
@@ -55,26 +23,48 @@ const CKSUM_INTS: [u32; 100] = [
     466108725, 639641238, 486777548, 986302837,
 ];
 
-const ALPHA_CODES: [char; 32] = [
-    '3', 'H', 'G', 'F', 'R', '6', '8', 'I', 'Q', 'W', 'J', '5', 'X', 'T', 'K', 'Z', 'A', 'Y', '7',
-    'O', '9', '4', 'P', 'D', 'U', 'C', 'E', 'S', 'M', 'N', 'B', 'L',
-];
-
 const N_CHARS: u8 = 18;
 const BITS_PER_CHAR: u8 = 5;
 const BYTE_LEN: usize = N_CHARS as usize * BITS_PER_CHAR as usize;
+const DIGITS_TO_READ: usize = BYTE_LEN - crate::CKSUM_BITS as usize;
+
+/// Checksum contribution of every possible byte, for each byte of the checksummed bits
+static CKSUM_TABLES: [[u32; 256]; DIGITS_TO_READ.div_ceil(8)] = cksum_tables();
+/// Unshuffled bits of every symbol, for each character of a full length password
+pub(crate) static CHAR_MASKS: [[u128; 32]; N_CHARS as usize] = char_masks();
 
-#[derive(Debug, PartialEq, Clone)]
-pub struct BinString(ArrayByteVec<BYTE_LEN>);
+/// A string of up to `BYTE_LEN` bits, packed into an integer.
+///
+/// The first bit of the string is the most significant bit of `bits`.
+/// Bits past `len` are always zero.
+#[derive(PartialEq, Eq, Clone)]
+pub struct BinString {
+    bits: u128,
+    len: u8,
+}
 
 impl BinString {
     pub fn zeroed() -> Self {
-        Self(ArrayByteVec::zeroed_with_len(BYTE_LEN))
+        Self {
+            bits: 0,
+            len: BYTE_LEN as u8,
+        }
     }
     pub fn from_raw(raw: [u8; BYTE_LEN]) -> Self {
-        Self(ArrayByteVec::from_raw(raw, BYTE_LEN))
+        Self::from_bytes(&raw)
     }
     pub fn from_alphanumeric(alnum: &[u8]) -> Self {
+        assert!(alnum.len() <= N_CHARS as usize);
+        if alnum.len() == N_CHARS as usize {
+            let mut bits = 0;
+            for (masks, &alpha_val) in CHAR_MASKS.iter().zip(alnum) {
+                bits |= masks[alnum_to_bin(alpha_val) as usize];
+            }
+            return Self {
+                bits,
+                len: BYTE_LEN as u8,
+            };
+        }
         let mut vec = ArrayByteVec::<BYTE_LEN>::zeroed_with_len(alnum.len() * 5);
         for (i, &alpha_val) in alnum.iter().enumerate() {
             let bin = alnum_to_bin(alpha_val);
@@ -83,148 +73,532 @@ impl BinString {
             }
         }
         unshuffle(&mut vec);
-        Self(vec)
-    }
-    pub fn to_alphanumeric(&self, len: usize) -> String {
-        let mut result = String::new();
-        let mut tmp = self.0.to_vec();
-        while tmp.len() < len * BITS_PER_CHAR as usize {
-            tmp.push(0);
-        }
-        tmp = shuffle(tmp);
-        for _ in 0..len {
-            let mut binary_char = tmp[0..BITS_PER_CHAR as usize].to_owned();
-            tmp = tmp[BITS_PER_CHAR as usize..].to_owned();
-            binary_char.extend_from_slice(
-                &[0; BITS_PER_CHAR as usize][0..BITS_PER_CHAR as usize - binary_char.len()],
-            );
-            let binary_char_idx = binary_string_to_int(&binary_char);
-            result.push(ALPHA_CODES[binary_char_idx as usize]);
-        }
-        result
+        Self::from_bytes(&vec)
     }
-    pub fn hash(&mut self, key: &BinString) {
-        assert!(self.0.len() <= BYTE_LEN);
-        for (sb, &kb) in self.0.iter_mut().zip(key.0.iter().cycle()) {
-            if kb == 1 {
-                if *sb == 0 {
-                    *sb = 1;
-                } else {
-                    *sb = 0;
-                }
+    /// Like `from_alphanumeric` for a full length password, but fails with the position of
+    /// the first character that's not part of the password alphabet
+    pub(crate) fn checked_from_password(pw: &crate::Password) -> Result<Self, usize> {
+        let mut bits = 0;
+        for (i, (masks, &alpha_val)) in CHAR_MASKS.iter().zip(pw).enumerate() {
+            match alphabet::to_symbol(alpha_val) {
+                None => return Err(i),
+                Some(bin) => bits |= masks[bin as usize],
+            }
+        }
+        Ok(Self {
+            bits,
+            len: BYTE_LEN as u8,
+        })
+    }
+    /// The packed bits, first bit of the string in the most significant bit
+    pub(crate) fn as_u128(&self) -> u128 {
+        self.bits
+    }
+    /// A full length bit string from packed bits, see `as_u128`
+    pub(crate) fn from_u128(bits: u128) -> Self {
+        Self {
+            bits,
+            len: BYTE_LEN as u8,
+        }
+    }
+    /// The first `len` bits of packed bits, see `as_u128`
+    #[cfg(feature = "alloc")]
+    pub(crate) fn from_u128_prefix(bits: u128, len: usize) -> Self {
+        assert!(len <= BYTE_LEN);
+        Self {
+            bits: bits & len_mask(len as u8),
+            len: len as u8,
+        }
+    }
+    fn from_bytes(bytes: &[u8]) -> Self {
+        let mut bits = 0;
+        for (i, &byte) in bytes.iter().enumerate() {
+            if byte != 0 {
+                bits |= 1 << (127 - i);
             }
         }
+        Self {
+            bits,
+            len: bytes.len() as u8,
+        }
+    }
+    fn to_bytes(&self) -> ArrayByteVec<BYTE_LEN> {
+        let mut vec = ArrayByteVec::zeroed_with_len(self.len as usize);
+        for i in 0..self.len {
+            vec[i] = self.bit(i);
+        }
+        vec
     }
-    pub fn reader(&self) -> Reader {
-        Reader { source: &self.0 }
+    /// Length in bits
+    pub fn len(&self) -> usize {
+        self.len as usize
     }
-    pub fn writer(&mut self) -> Writer {
-        Writer {
-            cursor: std::io::Cursor::new(&mut self.0),
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+    fn bit(&self, idx: u8) -> u8 {
+        (self.bits >> (127 - idx)) as u8 & 1
+    }
+    fn push_front(&mut self, bit: u8) {
+        assert!((self.len as usize) < BYTE_LEN);
+        self.bits = (bit as u128) << 127 | self.bits >> 1;
+        self.len += 1;
+    }
+    #[cfg(feature = "alloc")]
+    pub fn to_alphanumeric(&self, len: usize) -> alloc::string::String {
+        self.alphanumeric_chars(len).collect()
+    }
+    /// Like `to_alphanumeric`, but yields the characters instead of allocating a string
+    pub fn alphanumeric_chars(&self, len: usize) -> impl Iterator<Item = char> {
+        assert!(len <= N_CHARS as usize);
+        let mut tmp = ArrayByteVec::<BYTE_LEN>::zeroed_with_len(self.len().max(len * 5));
+        let bytes: &mut [u8] = &mut tmp;
+        bytes[..self.len()].copy_from_slice(&self.to_bytes());
+        shuffle(bytes);
+        (0..len).map(move |i| {
+            let bytes: &[u8] = &tmp;
+            let binary_char = &bytes[i * BITS_PER_CHAR as usize..][..BITS_PER_CHAR as usize];
+            alphabet::from_symbol(read_bin(binary_char) as u8) as char
+        })
+    }
+    /// The password for a full length bit string, without allocating
+    pub fn to_password(&self) -> crate::Password {
+        assert_eq!(self.len(), BYTE_LEN);
+        let mut pw = [0; N_CHARS as usize];
+        for (ch, out) in pw.iter_mut().enumerate() {
+            let sources = &SHUFFLE_90[ch * BITS_PER_CHAR as usize..][..BITS_PER_CHAR as usize];
+            let sym = sources.iter().fold(0, |sym, &src| sym << 1 | self.bit(src));
+            *out = alphabet::from_symbol(sym);
         }
+        pw
+    }
+    pub fn hash(&mut self, key: &BinString) {
+        self.bits ^= key.cycled(self.len);
+    }
+    pub fn hash_with(&mut self, key: &HashedKey) {
+        self.bits ^= key.mask & len_mask(self.len);
+    }
+    /// This bit string repeated over `len` bits
+    fn cycled(&self, len: u8) -> u128 {
+        if self.len == 0 {
+            return 0;
+        }
+        let mut mask = 0;
+        let mut pos = 0;
+        while pos < len {
+            mask |= self.bits >> pos;
+            pos += self.len;
+        }
+        mask & len_mask(len)
+    }
+    pub fn reader(&self) -> Reader<'_> {
+        Reader {
+            source: self,
+            pos: 0,
+        }
+    }
+    pub fn writer(&mut self) -> Writer<'_> {
+        Writer { dest: self, pos: 0 }
+    }
+    /// Show unhashed password bits grouped and labeled by field
+    pub fn explain(&self) -> Explain<'_> {
+        Explain(self)
     }
     pub fn calc_checksum(&self) -> u32 {
-        const DIGITS_TO_READ: u8 = N_CHARS * BITS_PER_CHAR - crate::CKSUM_BITS;
         const CHEKSUM_DIVISOR: u32 = 2u32.pow(crate::CKSUM_BITS as u32);
 
         let mut checksum = CKSUM_INTS[0];
-        let slice = &*self.0;
-        for i in 0..DIGITS_TO_READ {
-            checksum = checksum.wrapping_add(if slice[i as usize] == 0 {
-                CKSUM_INTS[i as usize]
-            } else {
-                CKSUM_INTS[i as usize + 17]
-            });
+        for (i, table) in CKSUM_TABLES.iter().enumerate() {
+            let byte = (self.bits >> (120 - i * 8)) as u8;
+            checksum = checksum.wrapping_add(table[byte as usize]);
         }
         checksum % CHEKSUM_DIVISOR
     }
 }
 
-fn binary_string_to_int(mut binstring: &[u8]) -> i32 {
-    let mut result = 0;
-    while !binstring.is_empty() {
-        result *= 2;
-        if binstring[0] == 1 {
-            result += 1;
+impl fmt::Debug for BinString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "BinString({})", Bits(self))
+    }
+}
+
+/// Bits of a `BinString` as zeros and ones
+struct Bits<'a>(&'a BinString);
+
+impl fmt::Display for Bits<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for i in 0..self.0.len {
+            f.write_char(if self.0.bit(i) == 1 { '1' } else { '0' })?;
         }
-        binstring = &binstring[1..];
+        Ok(())
     }
-    result
 }
 
-fn shuffle(mut input: Vec<u8>) -> Vec<u8> {
-    input = one_shuffle::<2>(input);
-    input = one_shuffle::<3>(input);
-    one_shuffle::<5>(input)
+#[cfg(feature = "serde")]
+impl serde::Serialize for BinString {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&Bits(self))
+    }
 }
 
-fn one_shuffle<const PARTS: usize>(input: Vec<u8>) -> Vec<u8> {
-    const V: Vec<u8> = Vec::new();
-    let mut vecs = [V; PARTS];
-    for (i, &byte) in input.iter().enumerate() {
-        let j = i % PARTS;
-        if j % 2 == 0 {
-            vecs[j].insert(0, byte);
-        } else {
-            vecs[j].push(byte);
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for BinString {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct BitsVisitor;
+
+        impl serde::de::Visitor<'_> for BitsVisitor {
+            type Value = BinString;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                write!(f, "a string of at most {} zeros and ones", BYTE_LEN)
+            }
+            fn visit_str<E: serde::de::Error>(self, v: &str) -> Result<BinString, E> {
+                let invalid = || E::invalid_value(serde::de::Unexpected::Str(v), &self);
+                if v.len() > BYTE_LEN {
+                    return Err(invalid());
+                }
+                let mut bs = BinString {
+                    bits: 0,
+                    len: v.len() as u8,
+                };
+                for (i, ch) in v.bytes().enumerate() {
+                    match ch {
+                        b'0' => {}
+                        b'1' => bs.bits |= 1 << (127 - i),
+                        _ => return Err(invalid()),
+                    }
+                }
+                Ok(bs)
+            }
+        }
+
+        deserializer.deserialize_str(BitsVisitor)
+    }
+}
+
+/// Field by field dump of unhashed password bits, made by `BinString::explain`
+pub struct Explain<'a>(&'a BinString);
+
+impl fmt::Display for Explain<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        const MAX_WIDTH: usize = layout::MYSTERY_BOX_STATUS.width as usize;
+
+        let bs = self.0;
+        if bs.len() != BYTE_LEN {
+            return write!(f, "{:?} is not a password, it has {} bits", bs, bs.len);
+        }
+        let mut reader = bs.reader();
+        for field in layout::fields(bs.bit(0) == 1) {
+            let start = reader.position();
+            let value = reader.next_int(field.width as usize);
+            write!(f, "{:>2} ", start)?;
+            for i in start..reader.position() {
+                f.write_char(if bs.bit(i as u8) == 1 { '1' } else { '0' })?;
+            }
+            for _ in field.width as usize..MAX_WIDTH {
+                f.write_char(' ')?;
+            }
+            write!(f, " {}: {}", field.name, value)?;
+            if field.id == FieldId::Checksum {
+                let cksum = bs.calc_checksum();
+                if u32::from(value) == cksum {
+                    f.write_str(" (matches)")?;
+                } else {
+                    write!(f, " (should be {})", cksum)?;
+                }
+            } else if !field.accepts(value) {
+                write!(f, " (must be between {} and {})", field.min, field.max)?;
+            }
+            f.write_char('\n')?;
+        }
+        Ok(())
+    }
+}
+
+/// Mask covering the first `len` bits of a `BinString`
+const fn len_mask(len: u8) -> u128 {
+    if len == 0 {
+        0
+    } else {
+        !0 << (128 - len as u32)
+    }
+}
+
+/// Read `len` bits starting at `pos` as an integer
+fn read_bits(bits: u128, pos: usize, len: usize) -> u16 {
+    if len == 0 {
+        0
+    } else {
+        ((bits << pos) >> (128 - len)) as u16
+    }
+}
+
+fn shuffle(input: &mut [u8]) {
+    let mut work_buffer = ArrayByteVec::<BYTE_LEN>::zeroed_with_len(input.len());
+    one_shuffle::<2>(input, &mut work_buffer);
+    one_shuffle::<3>(&work_buffer, input);
+    one_shuffle::<5>(input, &mut work_buffer);
+    input.copy_from_slice(&work_buffer);
+}
+
+/// Split `input` into `PARTS` interleaved parts, reverse the even ones, and concatenate them
+fn one_shuffle<const PARTS: usize>(input: &[u8], output: &mut [u8]) {
+    let mut out = output.iter_mut();
+    for j in 0..PARTS {
+        let count = (input.len() + PARTS - 1 - j) / PARTS;
+        for k in 0..count {
+            let k = if j.is_multiple_of(2) {
+                count - 1 - k
+            } else {
+                k
+            };
+            *out.next().unwrap() = input[k * PARTS + j];
+        }
+    }
+}
+
+/// Most characters the game lets a name have, spaces and dots included
+pub const MAX_NAME_LEN: usize = 17;
+
+/// Why a name can't be used
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum NameError {
+    /// Name has no significant characters
+    Empty,
+    /// Name has more than `MAX_NAME_LEN` characters
+    TooLong(usize),
+    /// Character at `pos` is not allowed in names
+    InvalidChar { pos: usize, ch: char },
+}
+
+impl fmt::Display for NameError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Empty => f.write_str("name is empty"),
+            Self::TooLong(len) => write!(
+                f,
+                "name has {} characters, can't have more than {}",
+                len, MAX_NAME_LEN
+            ),
+            Self::InvalidChar { pos, ch } => {
+                write!(f, "invalid character {:?} at position {}", ch, pos)
+            }
         }
     }
-    vecs.concat()
 }
 
+#[cfg(feature = "std")]
+impl std::error::Error for NameError {}
+
+/// Whether `ch` can be typed in a name: ASCII letters, spaces and dots
+pub fn is_name_char(ch: char) -> bool {
+    ch.is_ascii_alphabetic() || ch == ' ' || ch == '.'
+}
+
+/// Make a name key.
+///
+/// Only the first `MAX_NAME_LEN` significant characters are used, the rest is ignored.
+/// Bytes that aren't in the password alphabet, like 'V', hash the same as '3'.
+/// Use `try_hash_name` to reject names that the game wouldn't accept.
 pub fn hash_name(name: &[u8]) -> BinString {
-    let filtered: Vec<u8> = name.iter().cloned().filter_map(hash_filter_map).collect();
-    let mut hash_bin = BinString::from_alphanumeric(&filtered);
+    let mut filtered = [0; MAX_NAME_LEN];
+    let mut len = 0;
+    let significant = name.iter().cloned().filter_map(hash_filter_map);
+    for (slot, ch) in filtered.iter_mut().zip(significant) {
+        *slot = ch;
+        len += 1;
+    }
+    let mut hash_bin = BinString::from_alphanumeric(&filtered[..len]);
     // Avoid empty hash thingy
-    hash_bin.0.insert(0, 0);
-    hash_bin.0.insert(0, 1);
+    hash_bin.push_front(0);
+    hash_bin.push_front(1);
     hash_bin
 }
 
+/// Like `hash_name`, but fails for names that don't follow the game's name rules
+pub fn try_hash_name(name: &str) -> Result<BinString, NameError> {
+    let mut significant = 0;
+    for (pos, ch) in name.chars().enumerate() {
+        if !is_name_char(ch) {
+            return Err(NameError::InvalidChar { pos, ch });
+        }
+        if hash_filter_map(ch as u8).is_some() {
+            significant += 1;
+        }
+    }
+    match name.len() {
+        len if len > MAX_NAME_LEN => Err(NameError::TooLong(len)),
+        _ if significant == 0 => Err(NameError::Empty),
+        _ => Ok(hash_name(name.as_bytes())),
+    }
+}
+
+/// A name key already repeated over the length of a full password,
+/// for hashing many passwords with the same key.
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct HashedKey {
+    mask: u128,
+}
+
+impl HashedKey {
+    pub fn new(key: &BinString) -> Self {
+        Self {
+            mask: key.cycled(BYTE_LEN as u8),
+        }
+    }
+    /// The repeated key, packed like `BinString::as_u128`
+    #[cfg(feature = "alloc")]
+    pub(crate) fn as_u128(&self) -> u128 {
+        self.mask
+    }
+}
+
+impl From<&BinString> for HashedKey {
+    fn from(key: &BinString) -> Self {
+        Self::new(key)
+    }
+}
+
+/// Error from going past the end of a `BinString`, or from an integer that doesn't fit
+/// its field or a `u16`
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum BitError {
+    /// `len` bits starting at `pos` don't fit in the bit string
+    OutOfBounds { pos: usize, len: usize },
+    /// `value` doesn't fit in `digits` bits
+    Overflow { value: i32, digits: u8 },
+    /// `len` bits are more than can be read as one integer
+    TooWide { len: usize },
+}
+
+impl fmt::Display for BitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::OutOfBounds { pos, len } => {
+                write!(f, "{} bits at position {} are out of bounds", len, pos)
+            }
+            Self::Overflow { value, digits } => {
+                write!(f, "{} doesn't fit in {} bits", value, digits)
+            }
+            Self::TooWide { len } => write!(f, "can't read {} bits as one integer", len),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for BitError {}
+
+/// Check that `len` bits starting at `pos` are inside a bit string of `total` bits
+fn check_bounds(pos: usize, len: usize, total: u8) -> Result<(), BitError> {
+    match pos.checked_add(len) {
+        Some(end) if end <= total as usize => Ok(()),
+        _ => Err(BitError::OutOfBounds { pos, len }),
+    }
+}
+
 pub struct Reader<'a> {
-    source: &'a [u8],
+    source: &'a BinString,
+    pos: usize,
 }
 
 impl<'a> Reader<'a> {
     pub fn next_int(&mut self, len: usize) -> u16 {
-        let value = read_bin(&self.source[..len]);
-        self.advance(len);
-        value
+        self.try_next_int(len).unwrap()
+    }
+    /// Like `next_int`, but fails instead of reading past the end.
+    ///
+    /// `len` can be at most 16.
+    pub fn try_next_int(&mut self, len: usize) -> Result<u16, BitError> {
+        if len > 16 {
+            return Err(BitError::TooWide { len });
+        }
+        self.try_advance(len)?;
+        Ok(read_bits(self.source.bits, self.pos - len, len))
     }
     pub fn advance(&mut self, len: usize) {
-        self.source = &self.source[len..]
+        self.try_advance(len).unwrap()
+    }
+    pub fn try_advance(&mut self, len: usize) -> Result<(), BitError> {
+        check_bounds(self.pos, len, self.source.len)?;
+        self.pos += len;
+        Ok(())
+    }
+    /// Move to bit `pos`. The end of the string is a valid position.
+    pub fn seek(&mut self, pos: usize) -> Result<(), BitError> {
+        check_bounds(pos, 0, self.source.len)?;
+        self.pos = pos;
+        Ok(())
+    }
+    pub fn position(&self) -> usize {
+        self.pos
     }
     pub fn remaining(&self) -> usize {
-        self.source.len()
+        self.source.len as usize - self.pos
     }
 }
 
 pub struct Writer<'a> {
-    cursor: std::io::Cursor<&'a mut [u8]>,
+    dest: &'a mut BinString,
+    pos: usize,
 }
 
 impl<'a> Writer<'a> {
+    /// Write the lowest `DIGITS` bits of `int`
     pub fn write_int<const DIGITS: u8>(&mut self, int: i32) {
-        self.cursor
-            .write_all(&int_to_bin_string(int, DIGITS))
-            .unwrap();
+        let digits = DIGITS as usize;
+        check_bounds(self.pos, digits, self.dest.len).unwrap();
+        self.write_bits(int as u128, digits);
+    }
+    /// Like `write_int`, but fails if `int` doesn't fit in `DIGITS` bits,
+    /// or if it would be written past the end
+    pub fn try_write_int<const DIGITS: u8>(&mut self, int: i32) -> Result<(), BitError> {
+        let digits = DIGITS as usize;
+        if int < 0 || (digits < 32 && int >> digits != 0) {
+            return Err(BitError::Overflow {
+                value: int,
+                digits: DIGITS,
+            });
+        }
+        check_bounds(self.pos, digits, self.dest.len)?;
+        self.write_bits(int as u128, digits);
+        Ok(())
+    }
+    /// Like `try_write_int`, for widths only known at runtime
+    pub fn try_write_bits(&mut self, value: u16, digits: u8) -> Result<(), BitError> {
+        if digits < 16 && value >> digits != 0 {
+            return Err(BitError::Overflow {
+                value: value.into(),
+                digits,
+            });
+        }
+        check_bounds(self.pos, digits as usize, self.dest.len)?;
+        self.write_bits(value.into(), digits as usize);
+        Ok(())
+    }
+    fn write_bits(&mut self, value: u128, digits: usize) {
+        if digits == 0 {
+            return;
+        }
+        let shift = 128 - self.pos - digits;
+        let mask = (1u128 << digits) - 1;
+        self.dest.bits = self.dest.bits & !(mask << shift) | (value & mask) << shift;
+        self.pos += digits;
     }
     pub fn skip(&mut self, amount: u64) {
-        let pos = self.cursor.position();
-        self.cursor.set_position(pos + amount);
+        self.pos += amount as usize;
     }
-}
-
-fn int_to_bin_string(mut int: i32, n_digits: u8) -> Vec<u8> {
-    let mut result = Vec::new();
-    for _ in 0..n_digits {
-        result.insert(0, if int % 2 == 1 { 1 } else { 0 });
-        int /= 2;
+    /// Move to bit `pos`. The end of the string is a valid position.
+    pub fn seek(&mut self, pos: usize) -> Result<(), BitError> {
+        check_bounds(pos, 0, self.dest.len)?;
+        self.pos = pos;
+        Ok(())
+    }
+    pub fn position(&self) -> usize {
+        self.pos
     }
-    result
 }
 
-fn hash_filter_map(key: u8) -> Option<u8> {
+pub(crate) fn hash_filter_map(key: u8) -> Option<u8> {
     match key.to_ascii_uppercase() {
         b'0' | b'O' => Some(b'A'),
         b'1' | b'I' => Some(b'B'),
@@ -284,7 +658,7 @@ fn one_unshuffle<const PARTS: usize>(input: &[u8], output: &mut [u8]) {
     }
     for (i, out) in output.iter_mut().enumerate().take(input.len()) {
         let j = i % PARTS;
-        let value = if j % 2 == 0 {
+        let value = if j.is_multiple_of(2) {
             strs[j][strs[j].len() - i / PARTS - 1]
         } else {
             strs[j][i / PARTS]
@@ -294,20 +668,83 @@ fn one_unshuffle<const PARTS: usize>(input: &[u8], output: &mut [u8]) {
 }
 
 // Thank you Teddy for the awesome optimization!
+pub(crate) const UNSHUFFLE_90: [usize; BYTE_LEN] = [
+    2, 87, 81, 8, 14, 75, 33, 56, 62, 27, 21, 68, 38, 51, 45, 44, 50, 39, 69, 20, 26, 63, 57, 32,
+    74, 15, 9, 80, 86, 3, 1, 88, 82, 7, 13, 76, 34, 55, 61, 28, 22, 67, 37, 52, 46, 43, 49, 40, 70,
+    19, 25, 64, 58, 31, 73, 16, 10, 79, 85, 4, 0, 89, 83, 6, 12, 77, 35, 54, 60, 29, 23, 66, 36,
+    53, 47, 42, 48, 41, 71, 18, 24, 65, 59, 30, 72, 17, 11, 78, 84, 5,
+];
+
 fn unshuffle_90(input: &mut [u8]) {
-    const INDEX_VEC: [usize; BYTE_LEN] = [
-        2, 87, 81, 8, 14, 75, 33, 56, 62, 27, 21, 68, 38, 51, 45, 44, 50, 39, 69, 20, 26, 63, 57,
-        32, 74, 15, 9, 80, 86, 3, 1, 88, 82, 7, 13, 76, 34, 55, 61, 28, 22, 67, 37, 52, 46, 43, 49,
-        40, 70, 19, 25, 64, 58, 31, 73, 16, 10, 79, 85, 4, 0, 89, 83, 6, 12, 77, 35, 54, 60, 29,
-        23, 66, 36, 53, 47, 42, 48, 41, 71, 18, 24, 65, 59, 30, 72, 17, 11, 78, 84, 5,
-    ];
     let mut new = [0; BYTE_LEN];
     for i in 0..BYTE_LEN {
-        new[i] = input[INDEX_VEC[i]];
+        new[i] = input[UNSHUFFLE_90[i]];
     }
     input.copy_from_slice(&new);
 }
 
+const fn cksum_tables() -> [[u32; 256]; DIGITS_TO_READ.div_ceil(8)] {
+    let mut tables = [[0; 256]; DIGITS_TO_READ.div_ceil(8)];
+    let mut chunk = 0;
+    while chunk < tables.len() {
+        let mut byte = 0;
+        while byte < 256 {
+            let mut sum: u32 = 0;
+            let mut n = 0;
+            while n < 8 {
+                let i = chunk * 8 + n;
+                if i < DIGITS_TO_READ {
+                    let add = if byte & (0x80 >> n) == 0 {
+                        CKSUM_INTS[i]
+                    } else {
+                        CKSUM_INTS[i + 17]
+                    };
+                    sum = sum.wrapping_add(add);
+                }
+                n += 1;
+            }
+            tables[chunk][byte] = sum;
+            byte += 1;
+        }
+        chunk += 1;
+    }
+    tables
+}
+
+/// Where each bit of the shuffled string ends up after unshuffling
+static SHUFFLE_90: [u8; BYTE_LEN] = shuffle_90();
+
+const fn shuffle_90() -> [u8; BYTE_LEN] {
+    let mut dest = [0; BYTE_LEN];
+    let mut i = 0;
+    while i < BYTE_LEN {
+        dest[UNSHUFFLE_90[i]] = i as u8;
+        i += 1;
+    }
+    dest
+}
+
+const fn char_masks() -> [[u128; 32]; N_CHARS as usize] {
+    let dest = shuffle_90();
+    let mut masks = [[0; 32]; N_CHARS as usize];
+    let mut ch = 0;
+    while ch < N_CHARS as usize {
+        let mut sym = 0;
+        while sym < 32 {
+            let mut n = 0;
+            while n < BITS_PER_CHAR as usize {
+                if sym & (0b10000 >> n) != 0 {
+                    masks[ch][sym] |= 1 << (127 - dest[ch * BITS_PER_CHAR as usize + n] as usize);
+                }
+                n += 1;
+            }
+            sym += 1;
+        }
+        ch += 1;
+    }
+    masks
+}
+
 fn read_bin(bin: &[u8]) -> u16 {
     let mut result = 0;
     for &digit in bin {
@@ -367,37 +804,55 @@ fn test_unshuffle() {
 
 #[test]
 fn test_from_alnum() {
-    assert_eq!(&*BinString::from_alphanumeric(b"A").0, &[0, 1, 0, 0, 0]);
-    assert_eq!(&*BinString::from_alphanumeric(b"B").0, &[0, 1, 1, 1, 1]);
-    assert_eq!(&*BinString::from_alphanumeric(b"C").0, &[1, 1, 0, 0, 1]);
-    assert_eq!(&*BinString::from_alphanumeric(b"0").0, &[0, 0, 0, 0, 0]);
-    assert_eq!(&*BinString::from_alphanumeric(b"1").0, &[0, 0, 0, 0, 0]);
-    assert_eq!(&*BinString::from_alphanumeric(b"2").0, &[0, 0, 0, 0, 0]);
-    assert_eq!(
-        &*BinString::from_alphanumeric(b"000").0,
+    assert_eq!(
+        &*BinString::from_alphanumeric(b"A").to_bytes(),
+        &[0, 1, 0, 0, 0]
+    );
+    assert_eq!(
+        &*BinString::from_alphanumeric(b"B").to_bytes(),
+        &[0, 1, 1, 1, 1]
+    );
+    assert_eq!(
+        &*BinString::from_alphanumeric(b"C").to_bytes(),
+        &[1, 1, 0, 0, 1]
+    );
+    assert_eq!(
+        &*BinString::from_alphanumeric(b"0").to_bytes(),
+        &[0, 0, 0, 0, 0]
+    );
+    assert_eq!(
+        &*BinString::from_alphanumeric(b"1").to_bytes(),
+        &[0, 0, 0, 0, 0]
+    );
+    assert_eq!(
+        &*BinString::from_alphanumeric(b"2").to_bytes(),
+        &[0, 0, 0, 0, 0]
+    );
+    assert_eq!(
+        &*BinString::from_alphanumeric(b"000").to_bytes(),
         &[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]
     );
     assert_eq!(
-        &*BinString::from_alphanumeric(b"111").0,
+        &*BinString::from_alphanumeric(b"111").to_bytes(),
         &[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]
     );
     assert_eq!(
-        &*BinString::from_alphanumeric(b"222").0,
+        &*BinString::from_alphanumeric(b"222").to_bytes(),
         &[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]
     );
     assert_eq!(
-        &*BinString::from_alphanumeric(b"ABC").0,
+        &*BinString::from_alphanumeric(b"ABC").to_bytes(),
         &[1, 1, 1, 0, 1, 1, 0, 1, 0, 0, 0, 0, 0, 1, 1]
     );
     assert_eq!(
-        &*BinString::from_alphanumeric(b"ABC012").0,
+        &*BinString::from_alphanumeric(b"ABC012").to_bytes(),
         &[
             1, 0, 0, 0, 0, 0, 1, 0, 0, 0, 1, 0, 0, 0, 0, 1, 0, 0, 0, 1, 1, 0, 0, 1, 0, 1, 0, 0, 0,
             0
         ]
     );
     assert_eq!(
-        &*BinString::from_alphanumeric(b"AABBCCDDEEFFGGHHII").0,
+        &*BinString::from_alphanumeric(b"AABBCCDDEEFFGGHHII").to_bytes(),
         &[
             0, 1, 0, 0, 0, 0, 1, 0, 0, 0, 1, 1, 1, 0, 1, 0, 0, 1, 0, 1, 1, 1, 0, 1, 1, 1, 0, 0, 0,
             0, 0, 1, 1, 0, 1, 0, 1, 0, 0, 0, 0, 0, 1, 0, 1, 1, 0, 1, 0, 0, 1, 0, 1, 0, 0, 1, 1, 1,
@@ -406,7 +861,7 @@ fn test_from_alnum() {
         ]
     );
     assert_eq!(
-        &*BinString::from_alphanumeric(b"STUUBXGG5K8BY45ZN7").0,
+        &*BinString::from_alphanumeric(b"STUUBXGG5K8BY45ZN7").to_bytes(),
         &[
             0, 0, 1, 0, 0, 0, 1, 1, 0, 1, 1, 0, 1, 0, 0, 1, 0, 0, 1, 1, 1, 0, 1, 0, 1, 1, 1, 1, 0,
             1, 1, 1, 1, 1, 0, 1, 0, 1, 0, 0, 1, 1, 0, 1, 1, 1, 0, 0, 0, 0, 0, 1, 1, 0, 1, 1, 1, 1,
@@ -420,7 +875,7 @@ fn test_from_alnum() {
 fn test_hash() {
     let mut bs = BinString::from_alphanumeric(b"QWERTYUIOPASDFGHJK");
     assert_eq!(
-        &*bs.0,
+        &*bs.to_bytes(),
         &[
             0, 1, 1, 0, 0, 0, 0, 1, 1, 0, 1, 1, 1, 0, 1, 1, 1, 1, 1, 0, 0, 1, 0, 0, 0, 0, 1, 0, 1,
             0, 1, 1, 0, 0, 1, 0, 0, 1, 0, 0, 1, 0, 1, 0, 0, 1, 0, 1, 0, 0, 1, 1, 1, 1, 1, 0, 1, 1,
@@ -430,7 +885,7 @@ fn test_hash() {
     );
     bs.hash(&hash_name(b"Dew"));
     assert_eq!(
-        &*bs.0,
+        &*bs.to_bytes(),
         &[
             1, 1, 1, 1, 1, 1, 1, 1, 0, 1, 1, 0, 1, 0, 1, 0, 0, 0, 1, 0, 1, 0, 1, 1, 0, 1, 0, 0, 0,
             0, 1, 1, 1, 1, 0, 0, 0, 0, 1, 1, 0, 0, 0, 1, 0, 0, 0, 1, 0, 1, 0, 0, 1, 1, 0, 1, 0, 0,
@@ -453,3 +908,250 @@ fn test_read_bin() {
     assert_eq!(read_bin(&[0, 1, 1, 0, 1, 0, 1, 0, 1]), 213);
     assert_eq!(read_bin(&[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]), 1);
 }
+
+#[test]
+fn test_calc_checksum() {
+    // Bit by bit, the way the game does it
+    fn slow_checksum(bs: &BinString) -> u32 {
+        let mut checksum = CKSUM_INTS[0];
+        for i in 0..DIGITS_TO_READ {
+            checksum = checksum.wrapping_add(if bs.bit(i as u8) == 0 {
+                CKSUM_INTS[i]
+            } else {
+                CKSUM_INTS[i + 17]
+            });
+        }
+        checksum % 512
+    }
+    for pw in [
+        b"QWERTYUIOPASDFGHJK",
+        b"LLLLLLLLLLLLLLLLLL",
+        b"333333333333333333",
+    ]
+    .iter()
+    {
+        let bs = BinString::from_alphanumeric(*pw);
+        assert_eq!(bs.calc_checksum(), slow_checksum(&bs));
+    }
+}
+
+#[test]
+fn test_write_read_int() {
+    let mut bs = BinString::zeroed();
+    let mut writer = bs.writer();
+    writer.write_int::<3>(0b101);
+    writer.skip(2);
+    writer.write_int::<12>(4095);
+    writer.write_int::<4>(0b10011);
+    let mut reader = bs.reader();
+    assert_eq!(reader.next_int(3), 0b101);
+    assert_eq!(reader.next_int(2), 0);
+    assert_eq!(reader.next_int(12), 4095);
+    assert_eq!(reader.next_int(4), 0b0011);
+    assert_eq!(reader.remaining(), BYTE_LEN - 21);
+}
+
+#[test]
+fn test_hash_with() {
+    let key = hash_name(b"Dew");
+    let hashed_key = HashedKey::new(&key);
+    for pw in [b"QWERTYUIOPASDFGHJK", b"NICBRBINC8O4PKGOZK"].iter() {
+        let mut bs = BinString::from_alphanumeric(*pw);
+        let mut bs2 = bs.clone();
+        bs.hash(&key);
+        bs2.hash_with(&hashed_key);
+        assert_eq!(bs, bs2);
+    }
+}
+
+#[test]
+fn test_try_read_write() {
+    let mut bs = BinString::zeroed();
+    let mut writer = bs.writer();
+    assert_eq!(
+        writer.try_write_int::<12>(9999),
+        Err(BitError::Overflow {
+            value: 9999,
+            digits: 12
+        })
+    );
+    assert_eq!(
+        writer.try_write_int::<3>(-1),
+        Err(BitError::Overflow {
+            value: -1,
+            digits: 3
+        })
+    );
+    assert_eq!(
+        writer.try_write_bits(8, 3),
+        Err(BitError::Overflow {
+            value: 8,
+            digits: 3
+        })
+    );
+    assert_eq!(writer.try_write_int::<12>(4095), Ok(()));
+    assert_eq!(writer.seek(85), Ok(()));
+    assert_eq!(
+        writer.try_write_int::<6>(1),
+        Err(BitError::OutOfBounds { pos: 85, len: 6 })
+    );
+    assert_eq!(writer.try_write_int::<5>(0b11111), Ok(()));
+    assert_eq!(
+        writer.seek(91),
+        Err(BitError::OutOfBounds { pos: 91, len: 0 })
+    );
+    let mut reader = bs.reader();
+    assert_eq!(reader.try_next_int(12), Ok(4095));
+    assert_eq!(reader.try_next_int(17), Err(BitError::TooWide { len: 17 }));
+    assert_eq!(reader.position(), 12);
+    assert_eq!(reader.seek(88), Ok(()));
+    assert_eq!(
+        reader.try_next_int(3),
+        Err(BitError::OutOfBounds { pos: 88, len: 3 })
+    );
+    assert_eq!(reader.position(), 88);
+    assert_eq!(reader.try_next_int(2), Ok(0b11));
+    assert_eq!(reader.remaining(), 0);
+    let short = BinString::from_alphanumeric(b"ABC");
+    let mut reader = short.reader();
+    assert_eq!(
+        reader.try_advance(16),
+        Err(BitError::OutOfBounds { pos: 0, len: 16 })
+    );
+}
+
+#[test]
+fn test_hash_name_long() {
+    let long = hash_name(b"Wolfeschlegelsteinhausenbergerdorff");
+    assert_eq!(long.len(), MAX_NAME_LEN * 5 + 2);
+    assert_eq!(long, hash_name(b"Wolfeschlegelstei"));
+    assert_eq!(hash_name(b"D.e w"), hash_name(b"DEW"));
+    assert_eq!(hash_name("Dëw".as_bytes()).len(), 4 * 5 + 2);
+}
+
+#[test]
+fn test_try_hash_name() {
+    assert_eq!(try_hash_name("Dew"), Ok(hash_name(b"Dew")));
+    assert_eq!(try_hash_name("Mr. Dew"), Ok(hash_name(b"MrDew")));
+    assert_eq!(
+        try_hash_name("Wolfeschlegel Steinhausen"),
+        Err(NameError::TooLong(25))
+    );
+    assert_eq!(
+        try_hash_name("Wolfeschlegel.Ste"),
+        Ok(hash_name(b"WolfeschlegelSte"))
+    );
+    assert_eq!(
+        try_hash_name("Wolfeschlegel.Stei"),
+        Err(NameError::TooLong(18))
+    );
+    assert_eq!(try_hash_name(" . "), Err(NameError::Empty));
+    assert_eq!(
+        try_hash_name("Dëw"),
+        Err(NameError::InvalidChar { pos: 1, ch: 'ë' })
+    );
+    assert_eq!(
+        try_hash_name("D_w"),
+        Err(NameError::InvalidChar { pos: 1, ch: '_' })
+    );
+    assert_eq!(
+        try_hash_name("Mr. Dew 2"),
+        Err(NameError::InvalidChar { pos: 8, ch: '2' })
+    );
+}
+
+#[test]
+fn test_shuffle() {
+    for input in [
+        &[0, 1, 0, 0, 0][..],
+        &[1, 0, 0, 0, 0, 1, 1, 1, 1, 0, 1, 1, 0, 0, 1],
+    ]
+    .iter()
+    {
+        let mut buf = ArrayByteVec::<BYTE_LEN>::zeroed_with_len(input.len());
+        buf.copy_from_slice(input);
+        unshuffle(&mut buf);
+        shuffle(&mut buf);
+        assert_eq!(&*buf, *input);
+    }
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_to_alnum() {
+    for pw in ["QWERTYUIOPASDFGHJK", "NICBRBINC8O4PKGOZK", "AB"].iter() {
+        let bs = BinString::from_alphanumeric(pw.as_bytes());
+        assert_eq!(&bs.to_alphanumeric(pw.len()), pw);
+    }
+    assert_eq!(BinString::zeroed().to_alphanumeric(3), "333");
+}
+
+#[test]
+fn test_alnum_chars() {
+    let bs = BinString::from_alphanumeric(b"NICBRBINC8O4PKGOZK");
+    assert!(bs.alphanumeric_chars(18).eq("NICBRBINC8O4PKGOZK".chars()));
+    assert!(bs.alphanumeric_chars(0).eq("".chars()));
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_explain() {
+    use alloc::string::ToString;
+
+    let mut bs = BinString::from_alphanumeric(b"NEARBYSNOTTYSNEEZE");
+    bs.hash(&hash_name(b"DEW"));
+    let text = bs.explain().to_string();
+    let lines: alloc::vec::Vec<_> = text.lines().collect();
+    assert_eq!(lines.len(), 1 + 30 + 19);
+    assert_eq!(lines[0], " 0 0            All items: 0");
+    assert_eq!(lines[32], "33 111          Buizel: 7");
+    assert_eq!(lines[44], "65 0000110      Rank: 6");
+    assert!(lines[49].starts_with("81 ") && lines[49].ends_with("(matches)"));
+
+    let mut writer = bs.writer();
+    writer.seek(65).unwrap();
+    writer.write_int::<7>(66);
+    let text = bs.explain().to_string();
+    assert!(text.contains("Rank: 66 (must be between 0 and 65)"));
+    assert!(text.contains("(should be "));
+    assert_eq!(
+        BinString::from_alphanumeric(b"AB").explain().to_string(),
+        "BinString(1110100010) is not a password, it has 10 bits"
+    );
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_serde() {
+    let bs = BinString::from_alphanumeric(b"AB");
+    let json = serde_json::to_string(&bs).unwrap();
+    assert_eq!(json, "\"1110100010\"");
+    assert_eq!(serde_json::from_str::<BinString>(&json).unwrap(), bs);
+    assert!(serde_json::from_str::<BinString>("\"\"")
+        .unwrap()
+        .is_empty());
+    assert!(serde_json::from_str::<BinString>("\"0102\"").is_err());
+    let mut too_long = [b'0'; BYTE_LEN + 3];
+    too_long[0] = b'"';
+    too_long[BYTE_LEN + 2] = b'"';
+    assert!(serde_json::from_slice::<BinString>(&too_long).is_err());
+    too_long[BYTE_LEN + 1] = b'"';
+    assert!(serde_json::from_slice::<BinString>(&too_long[..BYTE_LEN + 2]).is_ok());
+}
+
+#[test]
+fn test_to_password() {
+    for pw in [
+        b"QWERTYUIOPASDFGHJK",
+        b"NICBRBINC8O4PKGOZK",
+        b"333333333333333333",
+    ]
+    .iter()
+    {
+        assert_eq!(BinString::from_alphanumeric(*pw).to_password(), **pw);
+    }
+    let mut bs = BinString::from_alphanumeric(b"NEARBYSNOTTYSNEEZE");
+    bs.hash(&hash_name(b"DEW"));
+    bs.hash(&hash_name(b"DEW"));
+    assert_eq!(&bs.to_password(), b"NEARBYSNOTTYSNEEZE");
+}
@@ -0,0 +1,130 @@
+use crate::{
+    layout::{self, Field, FieldId},
+    PlayerData,
+};
+use core::fmt;
+
+/// A field holding different values in two game states
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct FieldChange {
+    pub field: &'static Field,
+    pub old: u16,
+    pub new: u16,
+}
+
+impl fmt::Display for FieldChange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.field.id {
+            FieldId::Item(_) | FieldId::AllItems | FieldId::FivePeg | FieldId::SevenPeg => {
+                let change = if self.new > self.old {
+                    "gained"
+                } else {
+                    "lost"
+                };
+                write!(f, "{}: {}", self.field.name, change)
+            }
+            _ => write!(
+                f,
+                "{}: {} -> {} ({:+})",
+                self.field.name,
+                self.old,
+                self.new,
+                i32::from(self.new) - i32::from(self.old)
+            ),
+        }
+    }
+}
+
+/// Differences between two game states, in password order.
+///
+/// Displays one change per line.
+#[derive(Debug, Clone, Copy)]
+pub struct Diff<'a> {
+    old: &'a PlayerData,
+    new: &'a PlayerData,
+}
+
+impl<'a> Diff<'a> {
+    /// Changed fields. Item fields are only compared when both passwords store the same
+    /// ones, which depends on whether they have every item.
+    pub fn changes(&self) -> impl Iterator<Item = FieldChange> + 'a {
+        let (old, new) = (self.old, self.new);
+        let items: &'static [Field] = match (old.has_every_item(), new.has_every_item()) {
+            (true, true) => &layout::ALL_ITEMS_FIELDS,
+            (false, false) => &layout::ITEM_FIELDS,
+            _ => &[],
+        };
+        core::iter::once(&layout::ALL_ITEMS)
+            .chain(items)
+            .chain(layout::STATE_FIELDS.iter())
+            .filter_map(move |field| {
+                let change = FieldChange {
+                    field,
+                    old: old.get(field.id)?,
+                    new: new.get(field.id)?,
+                };
+                Some(change).filter(|change| change.old != change.new)
+            })
+    }
+    pub fn is_empty(&self) -> bool {
+        self.changes().next().is_none()
+    }
+}
+
+impl fmt::Display for Diff<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return f.write_str("no differences");
+        }
+        for (i, change) in self.changes().enumerate() {
+            if i > 0 {
+                f.write_str("\n")?;
+            }
+            write!(f, "{}", change)?;
+        }
+        Ok(())
+    }
+}
+
+/// Compare two decoded game states, for example from passwords for different names
+pub fn diff<'a>(old: &'a PlayerData, new: &'a PlayerData) -> Diff<'a> {
+    Diff { old, new }
+}
+
+#[test]
+fn test_diff() {
+    extern crate std;
+    use std::string::ToString;
+
+    let key = crate::binstring::hash_name(b"DEW");
+    let old = crate::decode(b"NICBRBINC8O4PKGOZK", &key).unwrap();
+    let new = crate::decode(b"NIYB8BINC8O98PGYKK", &key).unwrap();
+    assert!(diff(&old, &old).is_empty());
+    assert_eq!(diff(&old, &old).to_string(), "no differences");
+    assert_eq!(diff(&old, &new).to_string(), "Rank: 0 -> 11 (+11)");
+
+    let mut newer = new.clone();
+    newer.items[3] = true;
+    newer.chat_states[1] = 1;
+    newer.cash = 40;
+    newer.seven_peg = true;
+    let changes: std::vec::Vec<_> = diff(&new, &newer).changes().collect();
+    assert_eq!(changes.len(), 4);
+    assert_eq!(changes[0].field, &layout::ITEM_FIELDS[3]);
+    assert_eq!(
+        diff(&new, &newer).to_string(),
+        "Acetone: gained\nBuizel: 2 -> 1 (-1)\nCash: 0 -> 40 (+40)\n7 peg unlocked: gained"
+    );
+
+    // Only passwords with every item store the mystery box status
+    let mut every = new.clone();
+    every.items = [true; crate::player_data::ITEM_COUNT];
+    every.mystery_box_status = 5;
+    assert_eq!(diff(&new, &every).to_string(), "All items: gained");
+    let mut more_boxes = every.clone();
+    more_boxes.mystery_box_status = 7;
+    assert_eq!(
+        diff(&every, &more_boxes).to_string(),
+        "Mystery box status: 5 -> 7 (+2)"
+    );
+}
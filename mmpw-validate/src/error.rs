@@ -0,0 +1,88 @@
+use crate::{binstring::NameError, layout::Field};
+use core::fmt;
+
+/// Why a password was rejected
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ValidationError {
+    /// Password must be exactly `LEN` characters long
+    WrongLength(usize),
+    /// Character at `pos` is not part of the password alphabet
+    InvalidChar { pos: usize, ch: char },
+    /// All items flag is set, but the sentinel after it is not 326
+    BadSentinel(u16),
+    /// Puzzle rank can't be higher than 65
+    RankOutOfRange(u8),
+    /// Value doesn't fit in its field, or the game wouldn't accept it
+    OutOfRange { field: &'static Field, value: u16 },
+    /// Checksum stored in the password doesn't match the calculated one
+    BadChecksum { expected: u32, actual: u32 },
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::WrongLength(len) => write!(
+                f,
+                "password is {} characters long, must be {}",
+                len,
+                crate::LEN
+            ),
+            Self::InvalidChar { pos, ch } => {
+                write!(f, "invalid character {:?} at position {}", ch, pos)
+            }
+            Self::BadSentinel(value) => write!(
+                f,
+                "all items sentinel is {}, must be {}",
+                value,
+                crate::ALL_ITEMS_SENTINEL
+            ),
+            Self::RankOutOfRange(rank) => write!(
+                f,
+                "rank is {}, can't be higher than {}",
+                rank,
+                crate::MAX_RANK
+            ),
+            Self::OutOfRange { field, value } => write!(
+                f,
+                "{} is {}, must be between {} and {}",
+                field.name, value, field.min, field.max
+            ),
+            Self::BadChecksum { expected, actual } => {
+                write!(f, "checksum is {}, should be {}", actual, expected)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ValidationError {}
+
+/// Why a password couldn't be moved to another name
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum RekeyError {
+    /// The name the password was made for is not a valid name
+    OldName(NameError),
+    /// The name to move the password to is not a valid name
+    NewName(NameError),
+    /// The password is not valid for the old name
+    Password(ValidationError),
+}
+
+impl From<ValidationError> for RekeyError {
+    fn from(error: ValidationError) -> Self {
+        Self::Password(error)
+    }
+}
+
+impl fmt::Display for RekeyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::OldName(e) => write!(f, "invalid name: {}", e),
+            Self::NewName(e) => write!(f, "invalid new name: {}", e),
+            Self::Password(e) => write!(f, "invalid password: {}", e),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for RekeyError {}
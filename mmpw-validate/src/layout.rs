@@ -0,0 +1,218 @@
+//! Where each part of the game state is stored in an unhashed password.
+//!
+//! The password starts with the all items flag. If it's set, the mystery box status,
+//! the abra bead capacity and a sentinel follow, otherwise one flag for each item.
+//! Both branches are the same length, and the rest of the fields come after them.
+
+use crate::player_data::{CHAT_NAMES, CHAT_STATE_COUNT, ITEM_COUNT, ITEM_NAMES};
+
+/// Which part of the game state a field holds
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Hash)]
+pub enum FieldId {
+    AllItems,
+    MysteryBoxStatus,
+    AbraBeadCapacity,
+    AllItemsSentinel,
+    Item(u8),
+    ChatState(u8),
+    Cash,
+    AbraStory,
+    FinalTrialCount,
+    Rank,
+    TimePlayed,
+    FivePeg,
+    SevenPeg,
+    Unused,
+    Checksum,
+}
+
+/// A run of bits holding one value
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct Field {
+    pub id: FieldId,
+    pub name: &'static str,
+    pub width: u8,
+    /// Smallest value the game accepts
+    pub min: u16,
+    /// Largest value the game accepts
+    pub max: u16,
+}
+
+impl Field {
+    const fn new(id: FieldId, name: &'static str, width: u8) -> Self {
+        Self {
+            id,
+            name,
+            width,
+            min: 0,
+            max: (1 << width) - 1,
+        }
+    }
+    const fn with_range(mut self, min: u16, max: u16) -> Self {
+        self.min = min;
+        self.max = max;
+        self
+    }
+    pub fn accepts(&self, value: u16) -> bool {
+        (self.min..=self.max).contains(&value)
+    }
+    /// Whether some values that fit in the field are still invalid
+    const fn is_restricted(&self) -> bool {
+        self.min != 0 || self.max != (1 << self.width) - 1
+    }
+}
+
+pub const ALL_ITEMS: Field = Field::new(FieldId::AllItems, "All items", 1);
+pub const MYSTERY_BOX_STATUS: Field =
+    Field::new(FieldId::MysteryBoxStatus, "Mystery box status", 12);
+pub const ABRA_BEAD_CAPACITY: Field =
+    Field::new(FieldId::AbraBeadCapacity, "Abra bead capacity", 8);
+pub const ALL_ITEMS_SENTINEL: Field =
+    Field::new(FieldId::AllItemsSentinel, "All items sentinel", 10)
+        .with_range(crate::ALL_ITEMS_SENTINEL, crate::ALL_ITEMS_SENTINEL);
+pub const CASH: Field = Field::new(FieldId::Cash, "Cash", 6);
+pub const ABRA_STORY: Field = Field::new(FieldId::AbraStory, "Main story", 3);
+pub const FINAL_TRIAL_COUNT: Field = Field::new(FieldId::FinalTrialCount, "Final trial count", 3);
+pub const RANK: Field = Field::new(FieldId::Rank, "Rank", 7).with_range(0, crate::MAX_RANK as u16);
+pub const TIME_PLAYED: Field = Field::new(FieldId::TimePlayed, "Time played", 6);
+pub const FIVE_PEG: Field = Field::new(FieldId::FivePeg, "5 peg unlocked", 1);
+pub const SEVEN_PEG: Field = Field::new(FieldId::SevenPeg, "7 peg unlocked", 1);
+pub const UNUSED: Field = Field::new(FieldId::Unused, "Unused", 1);
+pub const CHECKSUM: Field = Field::new(FieldId::Checksum, "Checksum", crate::CKSUM_BITS);
+
+const CHAT_STATE_BITS: [u8; CHAT_STATE_COUNT] = [2, 3, 2, 2, 3, 2, 2, 2, 2, 2];
+
+/// Fields after the all items flag when it's set
+pub const ALL_ITEMS_FIELDS: [Field; 3] =
+    [MYSTERY_BOX_STATUS, ABRA_BEAD_CAPACITY, ALL_ITEMS_SENTINEL];
+/// Fields after the all items flag when it's not set
+pub const ITEM_FIELDS: [Field; ITEM_COUNT] = item_fields();
+/// Fields after the item fields
+pub const STATE_FIELDS: [Field; CHAT_STATE_COUNT + 9] = state_fields();
+
+const fn item_fields() -> [Field; ITEM_COUNT] {
+    let mut fields = [UNUSED; ITEM_COUNT];
+    let mut i = 0;
+    while i < ITEM_COUNT {
+        fields[i] = Field::new(FieldId::Item(i as u8), ITEM_NAMES[i], 1);
+        i += 1;
+    }
+    fields
+}
+
+const fn state_fields() -> [Field; CHAT_STATE_COUNT + 9] {
+    let mut fields = [UNUSED; CHAT_STATE_COUNT + 9];
+    let mut i = 0;
+    while i < CHAT_STATE_COUNT {
+        fields[i] = Field::new(
+            FieldId::ChatState(i as u8),
+            CHAT_NAMES[i],
+            CHAT_STATE_BITS[i],
+        );
+        i += 1;
+    }
+    let rest = [
+        CASH,
+        ABRA_STORY,
+        FINAL_TRIAL_COUNT,
+        RANK,
+        TIME_PLAYED,
+        FIVE_PEG,
+        SEVEN_PEG,
+        UNUSED,
+        CHECKSUM,
+    ];
+    let mut j = 0;
+    while j < rest.len() {
+        fields[i + j] = rest[j];
+        j += 1;
+    }
+    fields
+}
+
+/// Every field of a password, in order
+pub fn fields(all_items: bool) -> impl Iterator<Item = &'static Field> {
+    let items: &'static [Field] = if all_items {
+        &ALL_ITEMS_FIELDS
+    } else {
+        &ITEM_FIELDS
+    };
+    core::iter::once(&ALL_ITEMS)
+        .chain(items)
+        .chain(STATE_FIELDS.iter())
+}
+
+/// The field bit `pos` of a password belongs to
+pub fn field_at(all_items: bool, pos: usize) -> Option<&'static Field> {
+    let mut end = 0;
+    fields(all_items).find(|field| {
+        end += field.width as usize;
+        pos < end
+    })
+}
+
+/// Fields that can make a password invalid, with their offsets.
+///
+/// Validation only needs to look at these, everything else accepts any value.
+pub(crate) struct Checked {
+    fields: [(usize, Field); 3],
+    len: usize,
+}
+
+impl Checked {
+    pub(crate) fn fields(&self) -> &[(usize, Field)] {
+        &self.fields[..self.len]
+    }
+}
+
+/// Checked fields when the all items flag is set and when it isn't
+pub(crate) static CHECKED: [Checked; 2] = [checked(&ITEM_FIELDS), checked(&ALL_ITEMS_FIELDS)];
+
+const fn checked(items: &[Field]) -> Checked {
+    let mut checked = Checked {
+        fields: [(0, UNUSED); 3],
+        len: 0,
+    };
+    let mut offset = ALL_ITEMS.width as usize;
+    let mut i = 0;
+    while i < items.len() + STATE_FIELDS.len() {
+        let field = if i < items.len() {
+            items[i]
+        } else {
+            STATE_FIELDS[i - items.len()]
+        };
+        if field.is_restricted() || matches!(field.id, FieldId::Checksum) {
+            checked.fields[checked.len] = (offset, field);
+            checked.len += 1;
+        }
+        offset += field.width as usize;
+        i += 1;
+    }
+    checked
+}
+
+#[test]
+fn test_layout_len() {
+    for &all_items in [false, true].iter() {
+        let len: usize = fields(all_items).map(|f| f.width as usize).sum();
+        assert_eq!(len, crate::LEN as usize * 5);
+        let offset = |id| {
+            fields(all_items)
+                .take_while(|f| f.id != id)
+                .map(|f| f.width as usize)
+                .sum::<usize>()
+        };
+        assert_eq!(offset(FieldId::Rank), 65);
+        assert_eq!(offset(FieldId::Checksum), len - crate::CKSUM_BITS as usize);
+    }
+}
+
+#[test]
+fn test_field_at() {
+    assert_eq!(field_at(false, 0), Some(&ALL_ITEMS));
+    assert_eq!(field_at(false, 30).unwrap().id, FieldId::Item(29));
+    assert_eq!(field_at(true, 30), Some(&ALL_ITEMS_SENTINEL));
+    assert_eq!(field_at(true, 65), Some(&RANK));
+    assert_eq!(field_at(true, 89), Some(&CHECKSUM));
+    assert_eq!(field_at(true, 90), None);
+}
@@ -1,40 +1,223 @@
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+pub mod alphabet;
 mod array_byte_vec;
+mod batch;
 pub mod binstring;
+mod diff;
+mod error;
+pub mod layout;
+pub mod names;
+pub mod partial;
+pub mod player_data;
+#[cfg(feature = "alloc")]
+mod recover;
+#[cfg(feature = "alloc")]
+mod repair;
+#[cfg(feature = "serde")]
+pub mod serde_password;
 
-use binstring::BinString;
+pub use batch::{validate_batch, BATCH_LEN};
+use binstring::{BinString, HashedKey};
+use core::convert::TryInto;
+pub use diff::{diff, Diff, FieldChange};
+pub use error::{RekeyError, ValidationError};
+use layout::{Field, FieldId};
+pub use player_data::PlayerData;
+#[cfg(feature = "alloc")]
+pub use recover::{implausibility, recover, Recovery, MAX_RECOVERY_EDITS};
+#[cfg(feature = "alloc")]
+pub use repair::{repair_checksum, valid_edits, Change, Edit, MAX_EDITS};
 
 pub const LEN: u8 = 18;
-const PW_ITEM_COUNT: u8 = 30;
 pub const CKSUM_BITS: u8 = 9;
+pub const MAX_RANK: u8 = 65;
+pub const ALL_ITEMS_SENTINEL: u16 = 326;
 
 pub type Password = [u8; LEN as usize];
 
+pub trait ParsePassword: Sized {
+    /// Parse a password the way people type it.
+    ///
+    /// Lowercase letters are accepted, and whitespace and dashes between the
+    /// characters are ignored. Error positions count characters of `text`.
+    fn parse(text: &str) -> Result<Self, ValidationError>;
+}
+
+impl ParsePassword for Password {
+    fn parse(text: &str) -> Result<Self, ValidationError> {
+        normalize(text, alphabet::is_valid_char)
+    }
+}
+
+/// Uppercase `text` and drop whitespace and dashes, failing on characters `accept` rejects
+fn normalize(text: &str, accept: impl Fn(char) -> bool) -> Result<Password, ValidationError> {
+    let mut pw = [0; LEN as usize];
+    let mut len = 0;
+    for (pos, ch) in text.chars().enumerate() {
+        if ch.is_whitespace() || ch == '-' {
+            continue;
+        }
+        let upper = ch.to_ascii_uppercase();
+        if !accept(upper) {
+            return Err(ValidationError::InvalidChar { pos, ch });
+        }
+        if let Some(slot) = pw.get_mut(len) {
+            *slot = upper as u8;
+        }
+        len += 1;
+    }
+    if len != LEN as usize {
+        return Err(ValidationError::WrongLength(len));
+    }
+    Ok(pw)
+}
+
 pub fn validate(pw: &Password, key: &BinString) -> bool {
-    let mut bs = BinString::from_alphanumeric(pw);
-    bs.hash(key);
-    validate_bin(&bs)
+    check(pw, key).is_ok()
+}
+
+/// Like `validate`, but without expanding the key again for every password
+pub fn validate_with(pw: &Password, key: &HashedKey) -> bool {
+    check_with(pw, key).is_ok()
 }
 
 pub fn validate_bin(bs: &BinString) -> bool {
+    check_bin(bs).is_ok()
+}
+
+/// Like `validate`, but tells why the password is invalid
+pub fn check(pw: &[u8], key: &BinString) -> Result<(), ValidationError> {
+    check_with(pw, &HashedKey::new(key))
+}
+
+pub fn check_with(pw: &[u8], key: &HashedKey) -> Result<(), ValidationError> {
+    unhash(pw, key).and_then(|bs| check_bin(&bs))
+}
+
+/// Like `validate_bin`, but tells why the password is invalid
+pub fn check_bin(bs: &BinString) -> Result<(), ValidationError> {
+    if bs.len() != LEN as usize * 5 {
+        return Err(ValidationError::WrongLength(bs.len() / 5));
+    }
+    let all_items = bs.reader().next_int(1) == 1;
     let mut reader = bs.reader();
-    if reader.next_int(1) == 1 {
-        reader.advance(12 + 8);
-        if reader.next_int(10) != 326 {
-            return false;
+    for (offset, field) in layout::CHECKED[all_items as usize].fields() {
+        reader.seek(*offset).unwrap();
+        let value = reader.next_int(field.width as usize);
+        if field.id == FieldId::Checksum {
+            let cksum = bs.calc_checksum();
+            if u32::from(value) != cksum {
+                return Err(ValidationError::BadChecksum {
+                    expected: cksum,
+                    actual: value.into(),
+                });
+            }
+        } else if !field.accepts(value) {
+            return Err(range_error(field, value));
         }
-    } else {
-        reader.advance(PW_ITEM_COUNT as usize);
     }
-    reader.advance(2 + 3 + 2 + 2 + 3 + 2 + 2 + 2 + 2 + 2 + 6 + 3 + 3);
-    let rank = reader.next_int(7);
-    if rank > 65 {
-        return false;
+    Ok(())
+}
+
+fn range_error(field: &'static Field, value: u16) -> ValidationError {
+    match field.id {
+        FieldId::AllItemsSentinel => ValidationError::BadSentinel(value),
+        FieldId::Rank => ValidationError::RankOutOfRange(value as u8),
+        _ => ValidationError::OutOfRange { field, value },
     }
-    if reader.remaining() > CKSUM_BITS as usize {
-        reader.advance(reader.remaining() - CKSUM_BITS as usize);
+}
+
+pub fn decode(pw: &Password, key: &BinString) -> Result<PlayerData, ValidationError> {
+    decode_bin(&unhash(pw, &HashedKey::new(key))?)
+}
+
+/// Turn a password into the game state bits it encodes
+fn unhash(pw: &[u8], key: &HashedKey) -> Result<BinString, ValidationError> {
+    let pw: &Password = pw
+        .try_into()
+        .map_err(|_| ValidationError::WrongLength(pw.len()))?;
+    let mut bs =
+        BinString::checked_from_password(pw).map_err(|pos| ValidationError::InvalidChar {
+            pos,
+            ch: pw[pos] as char,
+        })?;
+    bs.hash_with(key);
+    Ok(bs)
+}
+
+pub fn decode_bin(bs: &BinString) -> Result<PlayerData, ValidationError> {
+    check_bin(bs)?;
+    let mut data = PlayerData::default();
+    let all_items = bs.reader().next_int(1) == 1;
+    let mut reader = bs.reader();
+    for field in layout::fields(all_items) {
+        data.set(field.id, reader.next_int(field.width as usize));
     }
-    let parsed_cksum = reader.next_int(CKSUM_BITS as usize);
-    parsed_cksum as u32 == bs.calc_checksum()
+    Ok(data)
+}
+
+/// Turn game state into unhashed password bits
+pub fn encode(data: &PlayerData) -> Result<BinString, ValidationError> {
+    let mut bs = BinString::zeroed();
+    let mut writer = bs.writer();
+    for field in layout::fields(data.has_every_item()) {
+        let value = match field.id {
+            FieldId::AllItemsSentinel => ALL_ITEMS_SENTINEL,
+            FieldId::Checksum => continue,
+            id => data.get(id).unwrap_or(0),
+        };
+        if !field.accepts(value) {
+            return Err(range_error(field, value));
+        }
+        writer
+            .try_write_bits(value, field.width)
+            .expect("layout fits in a password");
+    }
+    write_checksum(&mut bs);
+    Ok(bs)
+}
+
+/// Store the checksum of the unhashed bits `bs` in its last `CKSUM_BITS` bits
+fn write_checksum(bs: &mut BinString) {
+    let cksum = bs.calc_checksum();
+    let mut writer = bs.writer();
+    writer
+        .seek(LEN as usize * 5 - CKSUM_BITS as usize)
+        .and_then(|_| writer.try_write_bits(cksum as u16, CKSUM_BITS))
+        .expect("checksum fits in a password");
+}
+
+/// Make a password for `new_name` that holds the same game state as `pw` does for `old_name`.
+///
+/// The checksum only covers the unhashed bits, so it carries over unchanged.
+pub fn rekey(pw: &Password, old_name: &str, new_name: &str) -> Result<Password, RekeyError> {
+    let old = binstring::try_hash_name(old_name).map_err(RekeyError::OldName)?;
+    let new = binstring::try_hash_name(new_name).map_err(RekeyError::NewName)?;
+    let mut bs = unhash(pw, &HashedKey::new(&old))?;
+    check_bin(&bs)?;
+    bs.hash(&new);
+    Ok(bs.to_password())
+}
+
+/// Passwords of random characters, the same ones every run
+#[cfg(test)]
+fn random_passwords() -> impl Iterator<Item = Password> {
+    // xorshift64
+    let mut rng = 0x2545_f491_4f6c_dd1du64;
+    core::iter::repeat_with(move || {
+        let mut pw = [0; LEN as usize];
+        for ch in pw.iter_mut() {
+            rng ^= rng << 13;
+            rng ^= rng >> 7;
+            rng ^= rng << 17;
+            *ch = alphabet::from_symbol(rng as u8);
+        }
+        pw
+    })
 }
 
 #[test]
@@ -53,6 +236,10 @@ fn test_validate() {
     assert!(validate(b"BLOBBYBIDOOFNEGATE", &key));
     assert!(validate(b"NIYB8BINC8O98PGYKK", &key));
     assert!(validate(b"NICBRBINC8O4PKGOZK", &key));
+    let hashed_key = HashedKey::new(&key);
+    assert!(!validate_with(b"88H4B75X8FR9C54577", &hashed_key));
+    assert!(validate_with(b"NEARBYSNOTTYSNEEZE", &hashed_key));
+    assert!(validate_with(b"NICBRBINC8O4PKGOZK", &hashed_key));
 }
 
 #[test]
@@ -72,3 +259,189 @@ fn test_validate_bin() {
     assert!(validate_bin(&bs2));
     assert!(validate_bin(&bs));
 }
+
+#[test]
+fn test_decode() {
+    let key = binstring::hash_name(b"DEW");
+    assert_eq!(
+        decode(b"88H4B75X8FR9C54577", &key),
+        Err(ValidationError::BadChecksum {
+            expected: 200,
+            actual: 71
+        })
+    );
+    let mut items = [false; player_data::ITEM_COUNT];
+    items[0] = true;
+    items[1] = true;
+    items[29] = true;
+    let expected = PlayerData {
+        items,
+        chat_states: [2; 10],
+        ..PlayerData::default()
+    };
+    assert_eq!(decode(b"NICBRBINC8O4PKGOZK", &key), Ok(expected.clone()));
+    assert_eq!(
+        decode(b"NIYB8BINC8O98PGYKK", &key),
+        Ok(PlayerData {
+            rank: 11,
+            ..expected
+        })
+    );
+    let data = decode(b"NEARBYSNOTTYSNEEZE", &key).unwrap();
+    assert_eq!(data.chat_states, [2, 7, 2, 1, 2, 1, 1, 3, 2, 0]);
+    assert_eq!(data.cash, 43);
+    assert_eq!(data.final_trial_count, 2);
+    assert_eq!(data.rank, 6);
+    assert_eq!(data.time_played, 19);
+    assert!(data.five_peg && data.seven_peg);
+}
+
+#[test]
+fn test_check() {
+    let key = binstring::hash_name(b"DEW");
+    assert_eq!(check(b"NEARBYSNOTTYSNEEZE", &key), Ok(()));
+    assert_eq!(
+        check(b"NEARBYSNOTTYSNEEZ", &key),
+        Err(ValidationError::WrongLength(17))
+    );
+    assert_eq!(
+        check(b"NEARBYSNOTTYSNEEZEE", &key),
+        Err(ValidationError::WrongLength(19))
+    );
+    assert_eq!(
+        check(b"NEARBYSN0TTYSNEEZE", &key),
+        Err(ValidationError::InvalidChar { pos: 8, ch: '0' })
+    );
+    assert_eq!(
+        check(b"nearbysnottysneeze", &key),
+        Err(ValidationError::InvalidChar { pos: 0, ch: 'n' })
+    );
+    assert!(matches!(
+        check(b"88H4B75X8FR9C54577", &key),
+        Err(ValidationError::BadChecksum { .. })
+    ));
+}
+
+#[test]
+fn test_check_bin() {
+    let mut bs = BinString::zeroed();
+    let mut writer = bs.writer();
+    writer.write_int::<1>(1);
+    writer.skip(12 + 8);
+    writer.write_int::<10>(325);
+    assert_eq!(check_bin(&bs), Err(ValidationError::BadSentinel(325)));
+    let mut bs = BinString::zeroed();
+    let mut writer = bs.writer();
+    writer.skip(65);
+    writer.write_int::<7>(66);
+    assert_eq!(check_bin(&bs), Err(ValidationError::RankOutOfRange(66)));
+    let bs = BinString::from_alphanumeric(b"NEARBYSNOTTY");
+    assert_eq!(check_bin(&bs), Err(ValidationError::WrongLength(12)));
+}
+
+#[test]
+fn test_parse_password() {
+    assert_eq!(
+        Password::parse("NEARBYSNOTTYSNEEZE"),
+        Ok(*b"NEARBYSNOTTYSNEEZE")
+    );
+    assert_eq!(
+        Password::parse("nearby snotty sneeze"),
+        Ok(*b"NEARBYSNOTTYSNEEZE")
+    );
+    assert_eq!(
+        Password::parse(" Nic-BRB-INC-8O4-PKG-OZK\n"),
+        Ok(*b"NICBRBINC8O4PKGOZK")
+    );
+    assert_eq!(
+        Password::parse("nearby snotty sneez"),
+        Err(ValidationError::WrongLength(17))
+    );
+    assert_eq!(
+        Password::parse("nearby snotty sneezes!"),
+        Err(ValidationError::InvalidChar { pos: 21, ch: '!' })
+    );
+    assert_eq!(
+        Password::parse("nearby snotty sneezes"),
+        Err(ValidationError::WrongLength(19))
+    );
+    assert_eq!(
+        Password::parse("VERY"),
+        Err(ValidationError::InvalidChar { pos: 0, ch: 'V' })
+    );
+    assert_eq!(
+        Password::parse("NEARBY SN0TTY SNEEZE"),
+        Err(ValidationError::InvalidChar { pos: 9, ch: '0' })
+    );
+    assert_eq!(
+        Password::parse("NEARBY SNÖTTY SNEEZE"),
+        Err(ValidationError::InvalidChar { pos: 9, ch: 'Ö' })
+    );
+    assert_eq!(Password::parse(""), Err(ValidationError::WrongLength(0)));
+}
+
+#[test]
+fn test_encode() {
+    let key = binstring::hash_name(b"DEW");
+    for pw in [
+        b"NEARBYSNOTTYSNEEZE",
+        b"NICBRBINC8O4PKGOZK",
+        b"NIYB8BINC8O98PGYKK",
+    ]
+    .iter()
+    {
+        let data = decode(pw, &key).unwrap();
+        let mut bs = encode(&data).unwrap();
+        assert_eq!(decode_bin(&bs), Ok(data));
+        bs.hash(&key);
+        assert_eq!(bs.to_password(), **pw);
+    }
+    let data = PlayerData {
+        mystery_box_status: 4095,
+        abra_bead_capacity: 255,
+        items: [true; player_data::ITEM_COUNT],
+        rank: MAX_RANK,
+        ..PlayerData::default()
+    };
+    assert_eq!(decode_bin(&encode(&data).unwrap()), Ok(data.clone()));
+    assert_eq!(
+        encode(&PlayerData {
+            mystery_box_status: 9999,
+            ..data.clone()
+        }),
+        Err(ValidationError::OutOfRange {
+            field: &layout::MYSTERY_BOX_STATUS,
+            value: 9999
+        })
+    );
+    assert_eq!(
+        encode(&PlayerData { rank: 66, ..data }),
+        Err(ValidationError::RankOutOfRange(66))
+    );
+}
+
+#[test]
+fn test_rekey() {
+    let dew = binstring::hash_name(b"DEW");
+    let mary = binstring::hash_name(b"Mary");
+    let pw = rekey(b"NEARBYSNOTTYSNEEZE", "DEW", "Mary").unwrap();
+    assert_ne!(&pw, b"NEARBYSNOTTYSNEEZE");
+    assert!(validate(&pw, &mary));
+    assert_eq!(decode(&pw, &mary), decode(b"NEARBYSNOTTYSNEEZE", &dew));
+    assert_eq!(rekey(&pw, "Mary", "DEW").unwrap(), *b"NEARBYSNOTTYSNEEZE");
+    assert!(matches!(
+        rekey(b"88H4B75X8FR9C54577", "DEW", "Mary"),
+        Err(RekeyError::Password(ValidationError::BadChecksum { .. }))
+    ));
+    assert_eq!(
+        rekey(b"NEARBYSNOTTYSNEEZE", "D3W", "Mary"),
+        Err(RekeyError::OldName(binstring::NameError::InvalidChar {
+            pos: 1,
+            ch: '3'
+        }))
+    );
+    assert_eq!(
+        rekey(b"NEARBYSNOTTYSNEEZE", "DEW", " "),
+        Err(RekeyError::NewName(binstring::NameError::Empty))
+    );
+}
@@ -0,0 +1,175 @@
+//! Names that make the same key.
+//!
+//! Spaces and dots are ignored, case doesn't matter, `O` hashes like `A`, `I` like
+//! `B`, and `V`, which is not in the password alphabet, like `3`.
+//! Keys are also repeated over the whole password, so a name can make the same
+//! key as a shorter name whose key repeats the same way. The shorter name is
+//! usually not a prefix, because the bits of a name are shuffled depending on its
+//! length.
+
+use crate::binstring::{hash_name, HashedKey};
+#[cfg(feature = "alloc")]
+use crate::{
+    alphabet,
+    binstring::{hash_filter_map, try_hash_name, BinString, NameError},
+    LEN,
+};
+#[cfg(feature = "alloc")]
+use alloc::{string::String, vec::Vec};
+
+/// Whether passwords made for name `a` also work with name `b`
+pub fn names_equivalent(a: &str, b: &str) -> bool {
+    HashedKey::new(&hash_name(a.as_bytes())) == HashedKey::new(&hash_name(b.as_bytes()))
+}
+
+/// The letter every character hashing like `ch` is spelled as in canonical names
+#[cfg(feature = "alloc")]
+fn canonical_char(ch: u8) -> Option<u8> {
+    let folded = hash_filter_map(ch)?;
+    // 'V' is the only letter hashing like '3'
+    match alphabet::to_symbol(folded) {
+        Some(0) | None => Some(b'V'),
+        Some(_) => Some(folded),
+    }
+}
+
+/// Spell `name` with one character for each way a character can hash.
+///
+/// The result is uppercase without spaces or dots, and equal for names that only
+/// differ in characters hashing the same way.
+#[cfg(feature = "alloc")]
+pub fn canonical_name(name: &str) -> Result<String, NameError> {
+    try_hash_name(name)?;
+    Ok(name
+        .bytes()
+        .filter_map(canonical_char)
+        .map(char::from)
+        .collect())
+}
+
+/// The shortest canonical name equivalent to `name`
+#[cfg(feature = "alloc")]
+pub fn shortest_equivalent(name: &str) -> Result<String, NameError> {
+    let canonical = canonical_name(name)?;
+    let mask = HashedKey::new(&hash_name(name.as_bytes())).as_u128();
+    // Keys can have symbols no name character hashes like, for example the one of 'O'
+    let is_equivalent =
+        |short: &String| try_hash_name(short).is_ok() && names_equivalent(short, name);
+    Ok((1..canonical.len())
+        .filter_map(|len| name_with_mask(mask, len))
+        .find(is_equivalent)
+        .unwrap_or(canonical))
+}
+
+/// The canonical spelling of the `len` symbols whose key repeated over a password is `mask`
+#[cfg(feature = "alloc")]
+fn name_with_mask(mask: u128, len: usize) -> Option<String> {
+    let key_len = 2 + len * 5;
+    let bit = |pos: usize| mask >> (127 - pos) & 1;
+    // Keys start with 1 and 0, followed by the bits of the name characters
+    let repeats = (key_len..LEN as usize * 5).all(|pos| bit(pos) == bit(pos - key_len));
+    if bit(0) != 1 || bit(1) != 0 || !repeats {
+        return None;
+    }
+    let symbols = BinString::from_u128_prefix(mask << 2, len * 5).alphanumeric_chars(len);
+    Some(
+        symbols
+            .filter_map(|ch| canonical_char(ch as u8))
+            .map(char::from)
+            .collect(),
+    )
+}
+
+/// Every uppercase spelling of a canonical name without spaces or dots, in order.
+///
+/// Canonical names with characters no letter hashes like have no spellings.
+#[cfg(feature = "alloc")]
+pub fn spellings(canonical: &str) -> impl Iterator<Item = String> {
+    let choices: Vec<Vec<u8>> = canonical
+        .bytes()
+        .map(|ch| {
+            let ch = canonical_char(ch);
+            (b'A'..=b'Z')
+                .filter(|&other| canonical_char(other) == ch)
+                .collect()
+        })
+        .collect();
+    let mut next = if choices.iter().all(|c| !c.is_empty()) {
+        Some(alloc::vec![0; choices.len()])
+    } else {
+        None
+    };
+    core::iter::from_fn(move || {
+        let current = next.as_mut()?;
+        let spelling = current
+            .iter()
+            .zip(&choices)
+            .map(|(&i, c)| char::from(c[i]))
+            .collect();
+        // Count up like an odometer, the last character changing fastest
+        let done = current.iter_mut().zip(&choices).rev().all(|(i, c)| {
+            *i = (*i + 1) % c.len();
+            *i == 0
+        });
+        if done {
+            next = None;
+        }
+        Some(spelling)
+    })
+}
+
+#[test]
+fn test_names_equivalent() {
+    assert!(names_equivalent("Dew", "D.E W"));
+    assert!(names_equivalent("Otis", "atbs"));
+    assert!(names_equivalent("Vivi", "V.B VB"));
+    assert!(!names_equivalent("Dew", "DewV"));
+    assert!(!names_equivalent("Dew", "Drew"));
+    // Both keys repeat to the same bits over a password
+    assert!(names_equivalent("Sdknsd", "El"));
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_canonical_name() {
+    assert_eq!(canonical_name("Mr. Dew V").unwrap(), "MRDEWV");
+    assert_eq!(canonical_name("Otis").unwrap(), "ATBS");
+    assert_eq!(canonical_name("Vivi").unwrap(), "VBVB");
+    assert_eq!(canonical_name(" . "), Err(NameError::Empty));
+    assert_eq!(
+        canonical_name("J4"),
+        Err(NameError::InvalidChar { pos: 1, ch: '4' })
+    );
+    assert_eq!(shortest_equivalent("Mr. Dew").unwrap(), "MRDEW");
+    assert_eq!(shortest_equivalent("sl.pl sllltlltlpl").unwrap(), "L");
+    // Shorter names are usually not a prefix
+    assert!(names_equivalent("YL", "SACBMNLK"));
+    assert_eq!(shortest_equivalent("SACBMNLK").unwrap(), "YL");
+    assert_eq!(shortest_equivalent("Sdknsd").unwrap(), "EL");
+    // The key repeats like the one of "3E", which can't be typed
+    assert_eq!(shortest_equivalent("AAAUXUKG").unwrap(), "VE");
+    // The key repeats like the one of "I", but "I" hashes like "B"
+    let mask = HashedKey::new(&hash_name(b"S5BP6ZQ7")).as_u128();
+    assert_eq!(name_with_mask(mask, 1).unwrap(), "B");
+    assert!(!names_equivalent("I", "S5BP6ZQ7"));
+    assert!(!names_equivalent("B", "S5BP6ZQ7"));
+    assert_eq!(
+        shortest_equivalent("S5BP6ZQ7"),
+        Err(NameError::InvalidChar { pos: 1, ch: '5' })
+    );
+    for name in ["Otis", "Crumbling Statue", "ZZZZZZZZZZZZZZZZZ", "AAAUXUKG"].iter() {
+        let shortest = shortest_equivalent(name).unwrap();
+        assert!(try_hash_name(&shortest).is_ok());
+        assert!(names_equivalent(&shortest, name));
+    }
+}
+
+#[cfg(feature = "alloc")]
+#[test]
+fn test_spellings() {
+    let all: Vec<String> = spellings("BVW").collect();
+    assert_eq!(all, ["BVW", "IVW"]);
+    assert!(all.iter().all(|name| names_equivalent(name, "ivw")));
+    assert!(spellings("D.W").next().is_none());
+    assert!(spellings("D4W").next().is_none());
+}
@@ -0,0 +1,118 @@
+//! Validation of passwords put together from separately prepared parts.
+//!
+//! Every character of a password sets its own bits of the unhashed password, and
+//! every bit adds its own amount to the checksum. A run of characters can therefore
+//! be turned into bits and a checksum share once, and be combined with any other
+//! characters later on with an OR and an addition.
+
+use crate::{
+    alphabet,
+    batch::{CKSUM_BASE, DIGITS_TO_READ},
+    binstring::{BinString, HashedKey, CHAR_MASKS, CKSUM_INTS},
+    validate_bin, CKSUM_BITS, LEN,
+};
+
+/// Number of different checksum residues
+pub const RESIDUES: usize = 1 << CKSUM_BITS;
+
+const BIT_LEN: usize = LEN as usize * 5;
+
+/// Some characters of a password, already unhashed.
+///
+/// `residue` is how much the characters add to the calculated checksum, minus how
+/// much they add to the stored one. A whole password is valid when its
+/// `missing_residue` is 0 and its other fields are in range.
+#[derive(Debug, PartialEq, Eq, Clone, Copy, Default)]
+pub struct Partial {
+    bits: u128,
+    residue: u16,
+}
+
+impl Partial {
+    /// Prepare `chars` placed at position `pos` of a password for `key`.
+    ///
+    /// Returns `None` if a character is not in the alphabet.
+    ///
+    /// # Panics
+    ///
+    /// If `chars` doesn't fit in a password at `pos`.
+    pub fn new(chars: &[u8], pos: usize, key: &HashedKey) -> Option<Self> {
+        assert!(pos + chars.len() <= LEN as usize);
+        let mut bits = 0;
+        let mut covered = 0;
+        for (masks, &ch) in CHAR_MASKS[pos..].iter().zip(chars) {
+            bits |= masks[alphabet::to_symbol(ch)? as usize];
+            covered |= masks[0b11111];
+        }
+        let mut key_bits = BinString::zeroed();
+        key_bits.hash_with(key);
+        bits ^= key_bits.as_u128() & covered;
+        let mut residue = 0u32;
+        for bit in 0..BIT_LEN {
+            if bits >> (127 - bit) & 1 == 0 {
+                continue;
+            }
+            let share = if bit < DIGITS_TO_READ {
+                CKSUM_INTS[bit + 17].wrapping_sub(CKSUM_INTS[bit])
+            } else {
+                // Stored checksum bits count negatively, most significant first
+                (1u32 << (BIT_LEN - 1 - bit)).wrapping_neg()
+            };
+            residue = residue.wrapping_add(share);
+        }
+        Some(Self {
+            bits,
+            residue: (residue % RESIDUES as u32) as u16,
+        })
+    }
+    /// Both parts together. They must not overlap.
+    pub fn join(&self, other: &Self) -> Self {
+        Self {
+            bits: self.bits | other.bits,
+            residue: (self.residue + other.residue) % RESIDUES as u16,
+        }
+    }
+    pub fn residue(&self) -> u16 {
+        self.residue
+    }
+    /// The residue the rest of the password needs for the checksum to match
+    pub fn missing_residue(&self) -> u16 {
+        let have = (CKSUM_BASE % RESIDUES as u32) as u16 + self.residue;
+        (RESIDUES as u16 - have % RESIDUES as u16) % RESIDUES as u16
+    }
+    /// Whether this is a valid whole password
+    pub fn is_valid(&self) -> bool {
+        // The residue rules out most passwords without looking at any fields
+        self.missing_residue() == 0 && validate_bin(&BinString::from_u128(self.bits))
+    }
+}
+
+#[test]
+fn test_partial() {
+    let key = HashedKey::new(&crate::binstring::hash_name(b"DEW"));
+    let part = |chars: &[u8], pos| Partial::new(chars, pos, &key).unwrap();
+    let nearby = part(b"NEARBY", 0);
+    let snotty = part(b"SNOTTY", 6);
+    let prefix = nearby.join(&snotty);
+    let sneeze = part(b"SNEEZE", 12);
+    assert_eq!(sneeze.residue(), prefix.missing_residue());
+    assert!(prefix.join(&sneeze).is_valid());
+    assert!(!prefix.join(&part(b"SNEEZL", 12)).is_valid());
+    assert_eq!(part(b"NEARBYSNOTTYSNEEZE", 0), prefix.join(&sneeze));
+    assert_eq!(Partial::new(b"NE0RBY", 0, &key), None);
+
+    for pw in crate::random_passwords().take(500) {
+        let whole = part(&pw[..5], 0)
+            .join(&part(&pw[5..11], 5))
+            .join(&part(&pw[11..], 11));
+        assert_eq!(whole.is_valid(), crate::validate_with(&pw, &key));
+        let bs = crate::unhash(&pw, &key).unwrap();
+        let mut reader = bs.reader();
+        reader.advance(DIGITS_TO_READ);
+        let stored = reader.next_int(CKSUM_BITS as usize);
+        assert_eq!(
+            whole.missing_residue() == 0,
+            bs.calc_checksum() == u32::from(stored)
+        );
+    }
+}
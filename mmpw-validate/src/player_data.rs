@@ -0,0 +1,182 @@
+use crate::layout::FieldId;
+use core::convert::TryFrom;
+
+pub const ITEM_COUNT: usize = 30;
+pub const CHAT_STATE_COUNT: usize = 10;
+
+pub const ITEM_NAMES: [&str; ITEM_COUNT] = [
+    "Cornstarch Cookies",
+    "Graphing Calculator",
+    "Hydrogen Peroxide",
+    "Acetone",
+    "Romantic Incense",
+    "Puzzle Keys",
+    "Diamond Puzzle Key",
+    "Mars Software",
+    "Venus Software",
+    "Magnetic Desk Toy",
+    "Grey Anal Beads",
+    "Purple Anal Beads",
+    "Huge Glass Anal Beads",
+    "Huge Grey Anal Beads",
+    "Fruit Bugs",
+    "Marshmallow Bugs",
+    "Spooky Bugs",
+    "RetroPie",
+    "Assorted Gloves",
+    "Insulated Gloves",
+    "Ghost Gloves",
+    "Vanishing Gloves",
+    "Streaming Package",
+    "Vibrator",
+    "Blue Dildo",
+    "Gummy Dildo",
+    "Huge Dildo",
+    "Happy Meal Toys",
+    "Pizza Coupons",
+    "???",
+];
+
+/// Whose chat state is stored at each index of `chat_states`
+pub const CHAT_NAMES: [&str; CHAT_STATE_COUNT] = [
+    "Abra",
+    "Buizel",
+    "Heracross",
+    "Grovyle",
+    "Sandslash",
+    "Rhydon",
+    "Smeargle",
+    "Magnezone",
+    "Grimer",
+    "Lucario",
+];
+
+/// Game state stored in a password
+///
+/// With the `serde` feature, deserializing fails for values that can't be encoded.
+#[derive(Debug, Default, PartialEq, Eq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct PlayerData {
+    /// Only stored when every item is owned
+    pub mystery_box_status: u16,
+    /// Only stored when every item is owned
+    pub abra_bead_capacity: u8,
+    pub items: [bool; ITEM_COUNT],
+    pub chat_states: [u8; CHAT_STATE_COUNT],
+    /// Index into the password cash table
+    pub cash: u32,
+    pub abra_story: u8,
+    pub final_trial_count: u8,
+    pub rank: u8,
+    /// Index into the password time played table
+    pub time_played: u32,
+    pub five_peg: bool,
+    pub seven_peg: bool,
+}
+
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+#[serde(remote = "PlayerData")]
+struct UncheckedPlayerData {
+    mystery_box_status: u16,
+    abra_bead_capacity: u8,
+    items: [bool; ITEM_COUNT],
+    chat_states: [u8; CHAT_STATE_COUNT],
+    cash: u32,
+    abra_story: u8,
+    final_trial_count: u8,
+    rank: u8,
+    time_played: u32,
+    five_peg: bool,
+    seven_peg: bool,
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for PlayerData {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let data = UncheckedPlayerData::deserialize(deserializer)?;
+        crate::encode(&data).map_err(serde::de::Error::custom)?;
+        Ok(data)
+    }
+}
+
+impl PlayerData {
+    pub fn has_every_item(&self) -> bool {
+        self.items.iter().all(|&b| b)
+    }
+    /// Value of a field, or `None` if it isn't part of the game state.
+    ///
+    /// Values too big for a `u16` are returned as `u16::MAX`.
+    pub fn get(&self, id: FieldId) -> Option<u16> {
+        let value = match id {
+            FieldId::AllItems => self.has_every_item().into(),
+            FieldId::MysteryBoxStatus => self.mystery_box_status,
+            FieldId::AbraBeadCapacity => self.abra_bead_capacity.into(),
+            FieldId::Item(i) => self.items[i as usize].into(),
+            FieldId::ChatState(i) => self.chat_states[i as usize].into(),
+            FieldId::Cash => u16::try_from(self.cash).unwrap_or(u16::MAX),
+            FieldId::AbraStory => self.abra_story.into(),
+            FieldId::FinalTrialCount => self.final_trial_count.into(),
+            FieldId::Rank => self.rank.into(),
+            FieldId::TimePlayed => u16::try_from(self.time_played).unwrap_or(u16::MAX),
+            FieldId::FivePeg => self.five_peg.into(),
+            FieldId::SevenPeg => self.seven_peg.into(),
+            FieldId::AllItemsSentinel | FieldId::Unused | FieldId::Checksum => return None,
+        };
+        Some(value)
+    }
+    /// Set a field read from a password. Fields that aren't part of the
+    /// game state are ignored, and values are truncated to the field type.
+    pub fn set(&mut self, id: FieldId, value: u16) {
+        match id {
+            FieldId::AllItems => {
+                if value != 0 {
+                    self.items = [true; ITEM_COUNT];
+                }
+            }
+            FieldId::MysteryBoxStatus => self.mystery_box_status = value,
+            FieldId::AbraBeadCapacity => self.abra_bead_capacity = value as u8,
+            FieldId::Item(i) => self.items[i as usize] = value != 0,
+            FieldId::ChatState(i) => self.chat_states[i as usize] = value as u8,
+            FieldId::Cash => self.cash = value.into(),
+            FieldId::AbraStory => self.abra_story = value as u8,
+            FieldId::FinalTrialCount => self.final_trial_count = value as u8,
+            FieldId::Rank => self.rank = value as u8,
+            FieldId::TimePlayed => self.time_played = value.into(),
+            FieldId::FivePeg => self.five_peg = value != 0,
+            FieldId::SevenPeg => self.seven_peg = value != 0,
+            FieldId::AllItemsSentinel | FieldId::Unused | FieldId::Checksum => {}
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_serde() {
+    extern crate std;
+    use std::string::ToString;
+
+    let data = PlayerData {
+        items: [true; ITEM_COUNT],
+        mystery_box_status: 4095,
+        chat_states: [1; CHAT_STATE_COUNT],
+        cash: 43,
+        rank: 6,
+        seven_peg: true,
+        ..PlayerData::default()
+    };
+    let json = serde_json::to_value(&data).unwrap();
+    assert_eq!(json["mystery_box_status"], 4095);
+    assert_eq!(json["rank"], 6);
+    assert_eq!(
+        serde_json::from_value::<PlayerData>(json.clone()).unwrap(),
+        data
+    );
+    let mut bad = json.clone();
+    bad["rank"] = 66.into();
+    let err = serde_json::from_value::<PlayerData>(bad).unwrap_err();
+    assert_eq!(err.to_string(), "rank is 66, can't be higher than 65");
+    let mut bad = json;
+    bad["chat_states"][0] = 4.into();
+    assert!(serde_json::from_value::<PlayerData>(bad).is_err());
+}
@@ -0,0 +1,174 @@
+//! Guessing what password was meant when one was typed wrong.
+
+use crate::{
+    alphabet,
+    binstring::{BinString, HashedKey},
+    decode, normalize,
+    player_data::CHAT_STATE_COUNT,
+    validate_with, Password, PlayerData, ValidationError,
+};
+use alloc::vec::Vec;
+
+/// Most typos `recover` assumes were made
+pub const MAX_RECOVERY_EDITS: usize = 3;
+
+/// Main story stages mmpw-encode offers: the beginning, stages 1 to 4 and two endings
+const STORY_STAGES: u8 = 7;
+/// Stages mmpw-encode offers for each chat state, in `CHAT_NAMES` order
+const CHAT_STAGES: [u8; CHAT_STATE_COUNT] = [4, 5, 4, 4, 5, 4, 4, 4, 4, 4];
+
+/// A valid password that could have been meant
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Recovery {
+    pub password: Password,
+    pub data: PlayerData,
+    /// Typos needed to get from the typed password to this one
+    pub edits: usize,
+    /// Parts of `data` that are unlikely to come from a real save, see `implausibility`
+    pub oddities: usize,
+}
+
+impl Recovery {
+    /// Lower is more likely to be the intended password
+    pub fn score(&self) -> usize {
+        self.edits + self.oddities
+    }
+}
+
+/// Find the valid passwords `text` could be a mistyped version of, most plausible first.
+///
+/// Typos are characters swapped with a neighbour and characters mistaken for
+/// lookalikes, like `0` for `O` or `V` for `U`. Characters outside the alphabet
+/// are always assumed to be typos. `text` is normalized like `Password::parse`,
+/// and is returned as is if it's already valid.
+pub fn recover(text: &str, key: &BinString) -> Result<Vec<Recovery>, ValidationError> {
+    let typed = normalize(text, |ch| {
+        alphabet::is_valid_char(ch)
+            || ch.is_ascii() && alphabet::confusables(ch as u8).next().is_some()
+    })?;
+    let key_hash = HashedKey::new(key);
+    let mut found = Vec::new();
+    let mut visit = |candidate: &Password, edits| {
+        if validate_with(candidate, &key_hash) {
+            found.push((*candidate, edits));
+        }
+    };
+    let mut candidate = typed;
+    visit_lookalikes(&mut candidate, &typed, 0, MAX_RECOVERY_EDITS, 0, &mut visit);
+    for pos in 0..typed.len() - 1 {
+        if typed[pos] == typed[pos + 1] {
+            continue;
+        }
+        let mut swapped = typed;
+        swapped.swap(pos, pos + 1);
+        let mut candidate = swapped;
+        let edits = MAX_RECOVERY_EDITS - 1;
+        visit_lookalikes(&mut candidate, &swapped, 0, edits, 0, &mut |pw, used| {
+            visit(pw, used + 1)
+        });
+    }
+    // Keep the fewest edits when several typo combinations lead to the same password
+    found.sort_unstable();
+    found.dedup_by_key(|&mut (pw, _)| pw);
+    let mut recoveries: Vec<Recovery> = found
+        .into_iter()
+        .map(|(password, edits)| {
+            let data = decode(&password, key).unwrap();
+            Recovery {
+                password,
+                oddities: implausibility(&data),
+                data,
+                edits,
+            }
+        })
+        .collect();
+    recoveries.sort_by_key(|r| (r.score(), r.edits));
+    Ok(recoveries)
+}
+
+/// Count the story and chat stages in `data` that are past the ones mmpw-encode offers.
+///
+/// Passwords made by the game or the encoder don't have those, even though the
+/// fields have room for them.
+pub fn implausibility(data: &PlayerData) -> usize {
+    let story = usize::from(data.abra_story >= STORY_STAGES);
+    let chats = data
+        .chat_states
+        .iter()
+        .zip(CHAT_STAGES.iter())
+        .filter(|(&state, &stages)| state >= stages)
+        .count();
+    story + chats
+}
+
+/// Call `f` with every password that replaces at most `edits` characters of
+/// `typed` from `pos` on with lookalikes, and how many were replaced in total
+fn visit_lookalikes(
+    candidate: &mut Password,
+    typed: &Password,
+    pos: usize,
+    edits: usize,
+    used: usize,
+    f: &mut impl FnMut(&Password, usize),
+) {
+    if pos == candidate.len() {
+        f(candidate, used);
+        return;
+    }
+    let ch = typed[pos];
+    if alphabet::to_symbol(ch).is_some() {
+        visit_lookalikes(candidate, typed, pos + 1, edits, used, f);
+    }
+    if used < edits {
+        for other in alphabet::confusables(ch) {
+            candidate[pos] = other;
+            visit_lookalikes(candidate, typed, pos + 1, edits, used + 1, f);
+        }
+        candidate[pos] = ch;
+    }
+}
+
+#[test]
+fn test_recover() {
+    let key = crate::binstring::hash_name(b"DEW");
+    let found = recover("nearby snotty sneeze", &key).unwrap();
+    assert_eq!(&found[0].password, b"NEARBYSNOTTYSNEEZE");
+    assert_eq!(found[0].edits, 0);
+
+    let found = recover("NEARBY SN0TTY SNEEZE", &key).unwrap();
+    assert_eq!(&found[0].password, b"NEARBYSNOTTYSNEEZE");
+    assert_eq!(found[0].edits, 1);
+    assert!(found.windows(2).all(|w| w[0].score() <= w[1].score()));
+    for r in &found {
+        assert!(crate::validate(&r.password, &key));
+        assert_eq!(r.data, decode(&r.password, &key).unwrap());
+    }
+
+    let found = recover("NEARBY SNOTTY SNEZEE", &key).unwrap();
+    assert!(found
+        .iter()
+        .any(|r| &r.password == b"NEARBYSNOTTYSNEEZE" && r.edits == 1));
+
+    assert_eq!(
+        recover("NEARBY SNOTTY SNEEZ!", &key),
+        Err(ValidationError::InvalidChar { pos: 19, ch: '!' })
+    );
+    assert_eq!(
+        recover("NEARBY", &key),
+        Err(ValidationError::WrongLength(6))
+    );
+}
+
+#[test]
+fn test_implausibility() {
+    let mut data = PlayerData::default();
+    assert_eq!(implausibility(&data), 0);
+    data.abra_story = STORY_STAGES - 1;
+    data.chat_states = [3, 4, 3, 3, 4, 3, 3, 3, 3, 3];
+    assert_eq!(implausibility(&data), 0);
+    data.abra_story = STORY_STAGES;
+    assert_eq!(implausibility(&data), 1);
+    data.chat_states[1] = 5;
+    data.chat_states[9] = 4;
+    assert_eq!(implausibility(&data), 3);
+}
@@ -0,0 +1,212 @@
+use crate::{
+    alphabet,
+    binstring::{BinString, HashedKey},
+    check_bin,
+    layout::{self, Field},
+    unhash, validate_with, write_checksum, Password,
+};
+use alloc::vec::Vec;
+
+/// Most characters `valid_edits` is allowed to change
+pub const MAX_EDITS: usize = 2;
+
+/// Fix the checksum of `pw`, keeping the game state it holds.
+///
+/// The checksum is stored in the last `CKSUM_BITS` unhashed bits, which the shuffle
+/// spreads over a few characters. Those characters are rewritten to hold the checksum
+/// `calc_checksum` expects for the rest of the bits, so there is only one such password.
+/// It can differ from `pw` in every character holding checksum bits; `valid_edits`
+/// finds the passwords fewer characters away, which hold other game states.
+///
+/// Returns `pw` if it's already valid, and `None` if it has characters outside the
+/// alphabet or the rest of it is not a valid game state.
+pub fn repair_checksum(pw: &Password, key: &BinString) -> Option<Password> {
+    let mut bs = unhash(pw, &HashedKey::new(key)).ok()?;
+    write_checksum(&mut bs);
+    check_bin(&bs).ok()?;
+    bs.hash(key);
+    Some(bs.to_password())
+}
+
+/// Whether some checksum bits end up in the character at `pos`
+#[cfg(test)]
+fn holds_checksum(pos: usize) -> bool {
+    use crate::{CKSUM_BITS, LEN};
+    const CKSUM_MASK: u128 = ((1 << CKSUM_BITS) - 1) << (128 - LEN as usize * 5);
+
+    let mut pw = [alphabet::from_symbol(0); LEN as usize];
+    pw[pos] = alphabet::from_symbol(0b11111);
+    BinString::from_alphanumeric(&pw).as_u128() & CKSUM_MASK != 0
+}
+
+/// A valid password a few characters away from another one
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Edit {
+    pub password: Password,
+    /// Changed characters, from first to last
+    pub changes: Vec<Change>,
+}
+
+/// One changed character of an `Edit`
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct Change {
+    pub pos: usize,
+    pub from: u8,
+    pub to: u8,
+    /// Fields of the edited password whose bits the change flips
+    pub fields: Vec<&'static Field>,
+}
+
+/// List every valid password that differs from `pw` in 1 to `max_edits` characters,
+/// fewest changes first. `max_edits` is capped at `MAX_EDITS`.
+pub fn valid_edits(pw: &Password, key: &BinString, max_edits: usize) -> Vec<Edit> {
+    let hashed_key = HashedKey::new(key);
+    let mut found = Vec::new();
+    for edits in 1..=max_edits.min(MAX_EDITS) {
+        let mut candidate = *pw;
+        visit_edits(&mut candidate, pw, 0, edits, &mut |candidate| {
+            if validate_with(candidate, &hashed_key) {
+                found.push(describe_edit(pw, candidate, key));
+            }
+        });
+    }
+    found
+}
+
+fn describe_edit(original: &Password, edited: &Password, key: &BinString) -> Edit {
+    let mut unhashed = BinString::from_alphanumeric(edited);
+    unhashed.hash(key);
+    let all_items = unhashed.reader().next_int(1) == 1;
+    let original_bits = BinString::from_alphanumeric(original).as_u128();
+    let changes = (0..edited.len())
+        .filter(|&pos| original[pos] != edited[pos])
+        .map(|pos| {
+            let mut one_change = *original;
+            one_change[pos] = edited[pos];
+            let flipped = BinString::from_alphanumeric(&one_change).as_u128() ^ original_bits;
+            let mut fields: Vec<&'static Field> = Vec::new();
+            for bit in (0..unhashed.len()).filter(|bit| flipped >> (127 - bit) & 1 == 1) {
+                let field = layout::field_at(all_items, bit).unwrap();
+                if !fields.contains(&field) {
+                    fields.push(field);
+                }
+            }
+            Change {
+                pos,
+                from: original[pos],
+                to: edited[pos],
+                fields,
+            }
+        })
+        .collect();
+    Edit {
+        password: *edited,
+        changes,
+    }
+}
+
+/// Call `f` with every password that differs from `original` in exactly `edits`
+/// characters at or after `from`
+fn visit_edits(
+    candidate: &mut Password,
+    original: &Password,
+    from: usize,
+    edits: usize,
+    f: &mut impl FnMut(&Password),
+) {
+    if edits == 0 {
+        f(candidate);
+        return;
+    }
+    for pos in from..candidate.len() {
+        for ch in alphabet::charset() {
+            if ch == original[pos] {
+                continue;
+            }
+            candidate[pos] = ch;
+            visit_edits(candidate, original, pos + 1, edits - 1, f);
+        }
+        candidate[pos] = original[pos];
+    }
+}
+
+#[test]
+fn test_repair_checksum() {
+    let key = crate::binstring::hash_name(b"DEW");
+    assert_eq!(
+        repair_checksum(b"NEARBYSNOTTYSNEEZE", &key),
+        Some(*b"NEARBYSNOTTYSNEEZE")
+    );
+    for typo in [
+        b"NEARBYSNOTTYSNEEZL",
+        b"NEARBYSNOTTYSNEEZZ",
+        b"88H4B75X8FR9C54577",
+    ]
+    .iter()
+    {
+        let repaired = repair_checksum(typo, &key).unwrap();
+        assert!(validate_with(&repaired, &HashedKey::new(&key)));
+        for pos in 0..repaired.len() {
+            assert!(repaired[pos] == typo[pos] || holds_checksum(pos));
+        }
+    }
+    // Keeps the `L`, unlike the one character fix `valid_edits` finds
+    assert_eq!(
+        repair_checksum(b"NEARBYSNOTTYSNEEZL", &key),
+        Some(*b"NJA3BY5NOTTASTEEZL")
+    );
+    assert_eq!(repair_checksum(b"NEARBYSN0TTYSNEEZE", &key), None);
+    // Rank 66 is out of range, no checksum fixes that
+    let mut bs = crate::decode(b"NEARBYSNOTTYSNEEZE", &key)
+        .map(|data| crate::encode(&data).unwrap())
+        .unwrap();
+    let mut writer = bs.writer();
+    writer.skip(65);
+    writer.write_int::<7>(66);
+    bs.hash(&key);
+    assert_eq!(repair_checksum(&bs.to_password(), &key), None);
+}
+
+#[test]
+fn test_holds_checksum() {
+    let positions: Vec<usize> = (0..crate::LEN as usize)
+        .filter(|&pos| holds_checksum(pos))
+        .collect();
+    assert_eq!(positions, [1, 2, 3, 6, 11, 13, 14, 15, 16]);
+}
+
+#[test]
+fn test_valid_edits() {
+    let key = crate::binstring::hash_name(b"DEW");
+    let edits = valid_edits(b"NEARBYSNOTTYSNEEZL", &key, 1);
+    assert!(!edits.is_empty());
+    let fix = edits
+        .iter()
+        .find(|edit| &edit.password == b"NEARBYSNOTTYSNEEZE")
+        .unwrap();
+    assert_eq!(fix.changes.len(), 1);
+    let change = &fix.changes[0];
+    assert_eq!((change.pos, change.from, change.to), (17, b'L', b'E'));
+    assert!(!change.fields.is_empty());
+    for edit in &edits {
+        assert!(crate::validate(&edit.password, &key));
+    }
+    let edits = valid_edits(b"NEARBYSNOTTYSNEEZE", &key, 2);
+    assert!(edits
+        .iter()
+        .all(|edit| &edit.password != b"NEARBYSNOTTYSNEEZE"));
+    let first_double = edits
+        .iter()
+        .position(|edit| edit.changes.len() == 2)
+        .unwrap();
+    assert!(edits[first_double..]
+        .iter()
+        .all(|edit| edit.changes.len() == 2));
+    assert!(edits[..first_double]
+        .iter()
+        .all(|edit| edit.changes.len() == 1));
+    assert_eq!(
+        valid_edits(b"NEARBYSNOTTYSNEEZE", &key, MAX_EDITS + 1),
+        edits
+    );
+}
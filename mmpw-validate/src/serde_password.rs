@@ -0,0 +1,58 @@
+//! Serialize a `Password` as its 18 character string.
+//!
+//! `Password` is an array, so use this with `#[serde(with = "mmpw_validate::serde_password")]`.
+//! Deserializing accepts the same input as `ParsePassword::parse`.
+
+use crate::{ParsePassword, Password};
+use core::fmt;
+use serde::{de, ser, Deserializer, Serializer};
+
+pub fn serialize<S: Serializer>(pw: &Password, serializer: S) -> Result<S::Ok, S::Error> {
+    let text = core::str::from_utf8(pw)
+        .map_err(|_| ser::Error::custom("password contains non-ASCII bytes"))?;
+    serializer.serialize_str(text)
+}
+
+pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Password, D::Error> {
+    struct PasswordVisitor;
+
+    impl de::Visitor<'_> for PasswordVisitor {
+        type Value = Password;
+
+        fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "a {} character password", crate::LEN)
+        }
+        fn visit_str<E: de::Error>(self, v: &str) -> Result<Password, E> {
+            Password::parse(v).map_err(E::custom)
+        }
+    }
+
+    deserializer.deserialize_str(PasswordVisitor)
+}
+
+#[test]
+fn test_serde_password() {
+    extern crate std;
+    use std::string::ToString;
+
+    #[derive(serde::Serialize, serde::Deserialize, Debug, PartialEq)]
+    struct Entry {
+        #[serde(with = "crate::serde_password")]
+        password: Password,
+    }
+
+    let entry = Entry {
+        password: *b"NEARBYSNOTTYSNEEZE",
+    };
+    let json = serde_json::to_string(&entry).unwrap();
+    assert_eq!(json, r#"{"password":"NEARBYSNOTTYSNEEZE"}"#);
+    assert_eq!(serde_json::from_str::<Entry>(&json).unwrap(), entry);
+    assert_eq!(
+        serde_json::from_str::<Entry>(r#"{"password":"nearby snotty sneeze"}"#).unwrap(),
+        entry
+    );
+    let err = serde_json::from_str::<Entry>(r#"{"password":"NEARBY SN0TTY"}"#).unwrap_err();
+    assert!(err
+        .to_string()
+        .starts_with("invalid character '0' at position 9"));
+}
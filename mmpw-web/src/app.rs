@@ -2,16 +2,22 @@ use eframe::{
     egui::{self, Button, Color32, Label, ScrollArea, TextEdit, Visuals},
     epi,
 };
-use mmpw_gen::{WordError, WordPrepareError};
-use mmpw_validate::binstring;
+use mmpw_gen::{Search, WordError, WordPrepareError};
+use mmpw_validate::{alphabet, binstring, ParsePassword, Password, RekeyError};
 use std::fmt::Write;
 
+/// Most passwords `generate` lists, so a big word list doesn't freeze the page
+const MAX_GENERATED: usize = 1000;
+
 #[derive(Default)]
 pub struct App {
     passwords: String,
     words: String,
     name: String,
     err: Option<WordPrepareError>,
+    password: String,
+    new_name: String,
+    password_info: String,
 }
 
 impl epi::App for App {
@@ -34,6 +40,9 @@ impl epi::App for App {
             words,
             name,
             err,
+            password,
+            new_name,
+            password_info,
         } = self;
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.heading("Monster Mind Password tool");
@@ -41,6 +50,30 @@ impl epi::App for App {
                 ui.label("Name");
                 ui.text_edit_singleline(name);
             });
+            ui.horizontal(|ui| {
+                ui.label("Password");
+                ui.text_edit_singleline(password);
+                if ui
+                    .add(Button::new("Recover").enabled(!name.is_empty() && !password.is_empty()))
+                    .clicked()
+                {
+                    *password_info = recover(name, password);
+                }
+            });
+            ui.horizontal(|ui| {
+                ui.label("New name");
+                ui.text_edit_singleline(new_name);
+                let enabled = !name.is_empty() && !new_name.is_empty() && !password.is_empty();
+                if ui
+                    .add(Button::new("Move password to new name").enabled(enabled))
+                    .clicked()
+                {
+                    *password_info = rekey(name, new_name, password);
+                }
+            });
+            if !password_info.is_empty() {
+                ui.label(password_info.as_str());
+            }
             ui.separator();
             ui.horizontal(|ui| {
                 ui.label("6 letter words");
@@ -82,6 +115,9 @@ impl epi::App for App {
                         WordError::InvalidLength => buf.push_str("Invalid word length: Must be 6"),
                         WordError::InvalidChar(c) => {
                             let _ = write!(buf, "Invalid character: {}", *c as char);
+                            if let Some(valid) = alphabet::confusables(*c).next() {
+                                let _ = write!(buf, " (did you mean {}?)", valid as char);
+                            }
                         }
                     }
                     &buf
@@ -116,14 +152,69 @@ pub fn generate(name: &str, words: &str) -> Result<String, WordPrepareError> {
     let prepared_words = mmpw_gen::prepare_words(words.split_whitespace())?;
     let mut buf = String::new();
     let key = binstring::hash_name(name.as_bytes());
-    mmpw_gen::permutate(&key, &prepared_words, name, |pw, _name| {
-        let s = std::str::from_utf8(pw).unwrap();
+    // One more than shown, to know whether any were left out
+    let search = Search::new(&key, &prepared_words, name).max_results(MAX_GENERATED + 1);
+    for (i, found) in search.into_iter().enumerate() {
+        if i == MAX_GENERATED {
+            let _ = writeln!(buf, "(only the first {} shown)", MAX_GENERATED);
+            break;
+        }
+        let s = std::str::from_utf8(&found.password).unwrap();
         buf += &s[0..6];
         buf += " ";
         buf += &s[6..12];
         buf += " ";
         buf += &s[12..18];
         buf += "\n";
-    });
+    }
     Ok(buf)
 }
+
+/// List the passwords `typed` could have been meant to be, most plausible first
+pub fn recover(name: &str, typed: &str) -> String {
+    let key = match binstring::try_hash_name(name) {
+        Ok(key) => key,
+        Err(e) => return format!("Invalid name: {}", e),
+    };
+    let found = match mmpw_validate::recover(typed, &key) {
+        Ok(found) => found,
+        Err(e) => return format!("Invalid password: {}", e),
+    };
+    if found.is_empty() {
+        return "no similar valid password found".into();
+    }
+    let mut buf = String::new();
+    for recovery in found {
+        let s = std::str::from_utf8(&recovery.password).unwrap();
+        let _ = writeln!(
+            buf,
+            "{} {} {} ({} typos, rank {})",
+            &s[0..6],
+            &s[6..12],
+            &s[12..18],
+            recovery.edits,
+            recovery.data.rank
+        );
+    }
+    buf
+}
+
+/// Make `password` work with `new_name` instead of `name`
+pub fn rekey(name: &str, new_name: &str, password: &str) -> String {
+    let rekeyed = Password::parse(password)
+        .map_err(RekeyError::from)
+        .and_then(|pw| mmpw_validate::rekey(&pw, name, new_name));
+    match rekeyed {
+        Ok(pw) => {
+            let s = std::str::from_utf8(&pw).unwrap();
+            format!(
+                "Password for {}: {} {} {}",
+                new_name,
+                &s[0..6],
+                &s[6..12],
+                &s[12..18]
+            )
+        }
+        Err(e) => format!("Can't move password: {}", e),
+    }
+}